@@ -1 +0,0 @@
-echo "test script"
@@ -1,6 +1,26 @@
-use crate::{EndpointSetup, ExecutionPlan, NetworkSetup};
+use crate::{
+    EndpointSetup, ExecutionPlan, NetworkSetup,
+    endpoint::{Flow, ServerSetup},
+    error::Result,
+    network::{
+        Conditions,
+        hop::HopConditions,
+        nat::{Nat, PathChange, PathChangeKind},
+        netem::{Jitter, LossModel, Netem, Probability, Reorder},
+        offload::Offload,
+        outage::{Outage, OutageDirection},
+        pmtu::LinkMtu,
+        profile::{self, Profile},
+        queue::{BufferSize, Queue},
+        sysctl::{self, Sysctl, SysctlProfile},
+        topology::{IpVersion, NAME_PREFIX_LEN, Qdisc, Role, Topology},
+        trace::Trace,
+    },
+    report,
+};
 use byte_unit::Byte;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, error::ErrorKind, parser::ValueSource};
+use std::{path::PathBuf, time::Duration};
 use uuid::Uuid;
 
 /// Simple program to greet a person
@@ -13,27 +33,532 @@ struct Args {
     #[arg(short, default_value_t = 2)]
     run_count: u16,
 
-    /// Congestion Control algorithm
-    #[arg(long,  default_value_t = default_cc_algorithm())]
-    pub cc_algorithm: String,
+    /// Congestion Control algorithm. A comma-separated list starts one
+    /// server per algorithm, spread over the server nodes of the topology,
+    /// and assigns the flows to them in turn
+    #[arg(long, value_delimiter = ',', default_values_t = [default_cc_algorithm()])]
+    pub cc_algorithm: Vec<String>,
+
+    /// Server binary, as a comma-separated list with one entry per
+    /// `--cc-algorithm`. The last entry is used for the remaining servers
+    #[arg(long, value_delimiter = ',', default_values_t = [default_server_binary()])]
+    server_binary: Vec<String>,
+
+    /// Concurrent downloads per run, spread over the client namespaces.
+    /// Defaults to one per `--cc-algorithm` server, and cannot be fewer
+    #[arg(long)]
+    flows: Option<usize>,
+
+    /// Delay between the starts of consecutive flows (ms)
+    #[arg(long, default_value_t = 0)]
+    flow_stagger_ms: u64,
+
+    /// Named network conditions: `lte`, `3g`, `satellite`,
+    /// `transcontinental`, `wifi` or one of `--profile-file`. Flags given on
+    /// the command line override the profile
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// TOML file with more profiles, see profiles/builtin.toml
+    #[arg(long)]
+    profile_file: Option<PathBuf>,
+
+    /// One-way delay added by netem on the middle hop (ms)
+    #[arg(long, default_value_t = 50)]
+    delay_ms: u64,
+
+    /// Delay of the client to server direction, defaults to `--delay-ms`
+    #[arg(long)]
+    reverse_delay_ms: Option<u64>,
+
+    /// Loss model added by netem: `PCT`, `random:PCT[:CORR]`,
+    /// `gemodel:P[:R[:1-H[:1-K]]]` or `state:P13[:P31[:P32[:P23[:P14]]]]`
+    #[arg(long, default_value = "random:0")]
+    loss: LossModel,
+
+    /// Loss model of the client to server direction, e.g. for ACK loss.
+    /// Defaults to `--loss`
+    #[arg(long)]
+    reverse_loss: Option<LossModel>,
+
+    /// Delay variation added by netem: `MS[:CORR][:uniform|normal|pareto|paretonormal]`
+    #[arg(long)]
+    jitter: Option<Jitter>,
+
+    /// Packets sent without delay by netem: `PCT[:CORR[:GAP]]`
+    #[arg(long)]
+    reorder: Option<Reorder>,
+
+    /// Packets duplicated by netem: `PCT[:CORR]`
+    #[arg(long)]
+    duplicate: Option<Probability>,
+
+    /// Packets with a corrupted bit added by netem: `PCT[:CORR]`
+    #[arg(long)]
+    corrupt: Option<Probability>,
+
+    /// Rate of the htb bottleneck (mbit)
+    #[arg(long, default_value_t = 20)]
+    rate_mbit: u64,
+
+    /// Rate of the client to server direction, defaults to `--rate-mbit`
+    #[arg(long)]
+    reverse_rate_mbit: Option<u64>,
+
+    /// Leaf qdisc of the htb bottleneck: `pfifo`, `bfifo`, `fq_codel`, `pie`,
+    /// `cake`, `fq`, `dualpi2` or `red`, followed by its options, e.g.
+    /// `"fq_codel target 2ms"`. dualpi2 falls back to `red ecn`
+    #[arg(long, default_value = "pfifo")]
+    queue: Queue,
+
+    /// Bottleneck buffer: packets (`10800p`), bytes (`64kb`) or a multiple of
+    /// the bandwidth-delay product (`0.5bdp`)
+    #[arg(long, default_value = "10800p")]
+    buffer: BufferSize,
+
+    /// Police the server to client rate instead of shaping it: the htb hops
+    /// drop what exceeds `--rate-mbit` beyond a burst of packets (`40p`),
+    /// bytes (`64kb`) or a multiple of the bandwidth-delay product (`0.25bdp`)
+    /// rather than queueing it
+    #[arg(long, value_name = "BURST")]
+    police: Option<BufferSize>,
+
+    /// Bottleneck queue of the client to server direction, defaults to
+    /// `--queue`
+    #[arg(long)]
+    reverse_queue: Option<Queue>,
+
+    /// Bottleneck buffer of the client to server direction, defaults to
+    /// `--buffer`
+    #[arg(long)]
+    reverse_buffer: Option<BufferSize>,
+
+    /// Conditions of the server to client qdiscs of one router, overriding
+    /// the flags above on that hop: `NODE:KEY=VALUE[,KEY=VALUE...]` with the
+    /// keys `delay_ms`, `loss` and `jitter` for its netem qdisc and
+    /// `rate_mbit`, `queue`, `buffer` and `police` for its htb shaper. Repeat
+    /// for several hops
+    #[arg(long)]
+    hop: Vec<HopConditions>,
+
+    /// Topology description (TOML). Defaults to topologies/dumbbell.toml
+    #[arg(long)]
+    topology: Option<PathBuf>,
+
+    /// Address families of the topology: `4`, `6` or `dual`. IPv6 addresses
+    /// the topology does not give are derived from the IPv4 ones, and
+    /// endpoints connect over IPv6 unless this is `4`
+    #[arg(long, default_value = "4")]
+    ip_version: IpVersion,
+
+    /// Bandwidth/delay trace replayed on the server to client bottleneck
+    /// during each run.
+    /// `.csv` files hold `time_ms,rate_mbit,delay_ms` rows, anything else is
+    /// read as a Mahimahi trace
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Bin size used to turn a Mahimahi trace into rates (ms)
+    #[arg(long, default_value_t = 100)]
+    trace_bin_ms: u64,
+
+    /// 100% loss on the netem qdiscs during each run:
+    /// `START_MS:DURATION_MS[:forward|reverse|both]`, relative to the start
+    /// of the run. `both` drops everything and is the default. Repeat for
+    /// several outages
+    #[arg(long)]
+    outage: Vec<Outage>,
+
+    /// Masquerade the traffic this router forwards towards the server
+    #[arg(long, value_name = "NODE")]
+    nat: Option<String>,
+
+    /// Idle time after which the NAT forgets a UDP mapping (s)
+    #[arg(long, default_value_t = 30)]
+    nat_timeout_s: u64,
+
+    /// Change the client's path during each run: `AT_MS:rebind` makes the
+    /// NAT forget its mappings, `AT_MS:migrate` switches the clients to a
+    /// second address and back on the next migrate. Repeat for several
+    /// changes
+    #[arg(long)]
+    path_change: Vec<PathChange>,
+
+    /// MTU of both ends of the link with this interface: `IFACE:BYTES`, e.g.
+    /// `veth_m2_m3:1280` to emulate a tunnel. Repeat for several links
+    #[arg(long)]
+    mtu: Vec<LinkMtu>,
+
+    /// Drop the ICMP packet too big messages this router sends or forwards,
+    /// so that only DPLPMTUD finds a lower MTU
+    #[arg(long, value_name = "NODE")]
+    drop_packet_too_big: Option<String>,
+
+    /// Offloads of every interface: `KEY=on|off[,KEY=on|off...]` with the
+    /// keys `rx` and `tx` (checksums, on by default), `tso`, `gso`, `gro` and
+    /// `threaded_napi` (off by default). Batching lets senders burst past the
+    /// emulated rate
+    #[arg(long)]
+    offload: Option<Offload>,
+
+    /// Sysctl written in every namespace, e.g.
+    /// `net.ipv4.udp_rmem_min=8192`. Repeat for several sysctls
+    #[arg(long)]
+    sysctl: Vec<Sysctl>,
+
+    /// Host-wide sysctl written in the root namespace and restored on exit,
+    /// e.g. `net.core.rmem_max=8388608` to lift the cap on UDP socket buffers.
+    /// Repeat for several sysctls
+    #[arg(long)]
+    host_sysctl: Vec<Sysctl>,
+
+    /// Abort if the RTT or throughput measured before the runs deviates
+    /// from the plan by more than this (%)
+    #[arg(long, default_value_t = 20.0)]
+    preflight_tolerance_pct: f64,
+
+    /// Skip the pre-flight RTT and throughput check, e.g. with heavy loss
+    #[arg(long)]
+    skip_preflight: bool,
+
+    /// Build the network inside an unprivileged user namespace, so that boar
+    /// runs without sudo
+    #[arg(long)]
+    rootless: bool,
+
+    /// Capture a pcap of the server and client interfaces for every run, and
+    /// log the TLS keys to decrypt them, into the report directory
+    #[arg(long)]
+    capture: bool,
 }
 
 fn default_cc_algorithm() -> String {
     "bbr2_gcongestion".to_string()
 }
 
-pub(crate) fn parse() -> ExecutionPlan {
-    let args = Args::parse();
+fn default_server_binary() -> String {
+    // cargo build --example async_http3_server
+    "deps/quiche/target/debug/examples/async_http3_server".to_string()
+}
+
+pub(crate) fn parse() -> Result<ExecutionPlan> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let mut uuid = Uuid::new_v4();
+
+    let profile = match &args.profile {
+        Some(name) => {
+            let mut profiles = profile::catalog(args.profile_file.as_deref())?;
+            let Some(profile) = profiles.remove(name) else {
+                let known: Vec<_> = profiles.into_keys().collect();
+                Args::command()
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!("--profile {name}: expected one of {}", known.join(", ")),
+                    )
+                    .exit();
+            };
+            apply_profile(&mut args, &profile, |id| {
+                matches.value_source(id) == Some(ValueSource::CommandLine)
+            });
+            Some(profile)
+        }
+        None => None,
+    };
+
+    let topology = match &args.topology {
+        Some(path) => Topology::from_file(path)?,
+        None => Topology::dumbbell(),
+    }
+    .with_ip_version(args.ip_version);
+    // namespaces and veths of the plan, so that several plans can run at
+    // once. The prefix is drawn again while another plan uses it.
+    let (uuid, prefix) = loop {
+        let prefix = format!("{}_", &uuid.simple().to_string()[..NAME_PREFIX_LEN - 1]);
+        if !topology.clone().with_prefix(&prefix).in_use() {
+            break (uuid, prefix);
+        }
+        uuid = Uuid::new_v4();
+    };
+    let topology = topology.with_prefix(&prefix);
+    let trace = args
+        .trace
+        .as_ref()
+        .map(|path| Trace::from_file(path, Duration::from_millis(args.trace_bin_ms)))
+        .transpose()?;
 
-    cfg_if::cfg_if! {
-        if #[cfg(target_os = "linux")] {
-            let network_setup = "./scripts/virt_config_tc.sh".to_string();
-            let server_ip="10.55.10.1".to_string();
+    // validated when the topology is loaded
+    let server_nodes: Vec<_> = topology.servers().map(|node| node.name.clone()).collect();
+    let clients: Vec<_> = topology.clients().map(|node| node.name.clone()).collect();
+
+    if let Some(nat) = &args.nat {
+        let router = topology
+            .nodes
+            .iter()
+            .any(|node| node.name == format!("{prefix}{nat}") && node.role == Role::Router);
+        if !router {
+            Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("--nat {nat}: not a router of the topology"),
+                )
+                .exit();
+        }
+    }
+    for change in &args.path_change {
+        if change.kind == PathChangeKind::Rebind && args.nat.is_none() {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--path-change rebind requires --nat",
+                )
+                .exit();
+        }
+        if change.kind == PathChangeKind::Migrate {
+            for client in topology.clients() {
+                let iface = topology.node_ifaces(&client.name).next();
+                let migration_addrs: Vec<_> = iface
+                    .into_iter()
+                    .flat_map(|iface| [Some(iface.addr), iface.addr6])
+                    .flatten()
+                    .map(|addr| addr.last_host().ip)
+                    .collect();
+                let taken = topology.ifaces().any(|iface| {
+                    [Some(iface.addr), iface.addr6]
+                        .into_iter()
+                        .flatten()
+                        .any(|addr| migration_addrs.contains(&addr.ip))
+                });
+                if taken {
+                    Args::command()
+                        .error(
+                            ErrorKind::InvalidValue,
+                            format!(
+                                "--path-change migrate: the migration address of {} is taken",
+                                client.name
+                            ),
+                        )
+                        .exit();
+                }
+            }
+        }
+    }
+    let mut hops = args.hop;
+    for hop in &mut hops {
+        let user_name = hop.node.clone();
+        hop.node = format!("{prefix}{user_name}");
+        let router = topology
+            .nodes
+            .iter()
+            .any(|node| node.name == hop.node && node.role == Role::Router);
+        let has_forward = |has: fn(Qdisc) -> bool| {
+            topology
+                .node_ifaces(&hop.node)
+                .any(|iface| iface.qdisc.is_some_and(has) && topology.is_forward(iface))
+        };
+        let reason = if !router {
+            "not a router of the topology"
+        } else if hop.sets_netem() && !has_forward(Qdisc::delays) {
+            "delay_ms, loss and jitter need a server to client netem qdisc"
+        } else if hop.sets_htb() && !has_forward(Qdisc::shapes) {
+            "rate_mbit, queue, buffer and police need a server to client htb qdisc"
         } else {
-            let network_setup = "./scripts/test.sh".to_string();
-            let server_ip = "127.0.0.1".to_string();
+            continue;
+        };
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!("--hop {user_name}: {reason}"),
+            )
+            .exit();
+    }
+
+    if !args.outage.is_empty() && trace.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--outage and --trace both change the netem qdiscs and cannot be combined",
+            )
+            .exit();
+    }
+    for outage in &args.outage {
+        let has_netem = |forward| {
+            topology.ifaces().any(|iface| {
+                iface.qdisc.is_some_and(Qdisc::delays) && topology.is_forward(iface) == forward
+            })
+        };
+        if [true, false]
+            .into_iter()
+            .any(|forward| outage.covers(forward) && has_netem(forward))
+        {
+            continue;
         }
+        let direction = match outage.direction {
+            OutageDirection::Forward => "server to client ",
+            OutageDirection::Reverse => "client to server ",
+            OutageDirection::Both => "",
+        };
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!("--outage: the topology has no {direction}netem qdisc to drop packets on"),
+            )
+            .exit();
+    }
+
+    // IPv6 needs at least 1280 bytes on every link
+    let min_mtu = if matches!(args.ip_version, IpVersion::V4) {
+        68
+    } else {
+        1280
+    };
+    let mut mtus = args.mtu;
+    for link_mtu in &mut mtus {
+        let user_name = link_mtu.iface.clone();
+        link_mtu.iface = format!("{prefix}{user_name}");
+        let reason = if !topology.ifaces().any(|iface| iface.name == link_mtu.iface) {
+            "not an interface of the topology".to_string()
+        } else if !(min_mtu..=65535).contains(&link_mtu.mtu) {
+            format!("expected {min_mtu} to 65535 bytes")
+        } else {
+            continue;
+        };
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!("--mtu {user_name}: {reason}"),
+            )
+            .exit();
     }
+    let packet_too_big_dropped = args.drop_packet_too_big.map(|node| {
+        let router = topology
+            .nodes
+            .iter()
+            .any(|n| n.name == format!("{prefix}{node}") && n.role == Role::Router);
+        if !router {
+            Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("--drop-packet-too-big {node}: not a router of the topology"),
+                )
+                .exit();
+        }
+        format!("{prefix}{node}")
+    });
+
+    if !hops.is_empty() && trace.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--trace replays one rate and delay on every hop and cannot be combined with --hop",
+            )
+            .exit();
+    }
+    if args.police.is_some() && trace.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--trace replays its rates on htb shapers and cannot be combined with a policer",
+            )
+            .exit();
+    }
+
+    let offload = args.offload.unwrap_or_default();
+    if offload.threaded_napi && !offload.gro {
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                "--offload threaded_napi=on needs gro=on, veth only polls with NAPI while GRO is on",
+            )
+            .exit();
+    }
+
+    if args.rootless && !args.host_sysctl.is_empty() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--host-sysctl needs root in the root namespace, it cannot be combined with --rootless",
+            )
+            .exit();
+    }
+    if !args.host_sysctl.is_empty() && sysctl::host_in_use() {
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                "--host-sysctl: another boar run holds the host sysctls until it restores them",
+            )
+            .exit();
+    }
+    let sysctls = SysctlProfile::new(topology.has_ipv6()).with(args.sysctl, args.host_sysctl);
+
+    let nat = args.nat.map(|node| Nat {
+        node: format!("{prefix}{node}"),
+        mapping_timeout: Duration::from_secs(args.nat_timeout_s),
+    });
+
+    if args.server_binary.len() > args.cc_algorithm.len() {
+        Args::command()
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                "--server-binary: more binaries than --cc-algorithm servers",
+            )
+            .exit();
+    }
+    let servers: Vec<_> = args
+        .cc_algorithm
+        .into_iter()
+        .zip(9999..)
+        .enumerate()
+        .map(|(i, (cca, port))| {
+            let ns = server_nodes[i % server_nodes.len()].clone();
+            cfg_if::cfg_if! {
+                if #[cfg(target_os = "linux")] {
+                    let ip = topology.node_addr(&ns).expect("server address").ip;
+                } else {
+                    let ip = std::net::Ipv4Addr::LOCALHOST.into();
+                }
+            }
+            let binary = args.server_binary[i.min(args.server_binary.len() - 1)].clone();
+            ServerSetup {
+                binary,
+                ns,
+                ip,
+                port,
+                cca,
+            }
+        })
+        .collect();
+    let flow_count = args.flows.unwrap_or(servers.len());
+    if flow_count < servers.len() {
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "--flows {flow_count}: every one of the {} --cc-algorithm servers needs a flow",
+                    servers.len()
+                ),
+            )
+            .exit();
+    }
+    if args.flow_stagger_ms > 0 && flow_count > clients.len() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--flow-stagger-ms: the fairness of staggered flows is measured while all of them run, \
+                     which needs a client namespace per flow, but {flow_count} flows share {} clients",
+                    clients.len()
+                ),
+            )
+            .exit();
+    }
+    let flows = (0..flow_count)
+        .map(|i| Flow {
+            client_ns: clients[i % clients.len()].clone(),
+            server: i % servers.len(),
+            start_offset: Duration::from_millis(args.flow_stagger_ms * i as u64),
+        })
+        .collect();
 
     let run_setup = EndpointSetup {
         // Client
@@ -42,21 +567,89 @@ pub(crate) fn parse() -> ExecutionPlan {
         client_logging: "RUST_LOG=info".to_string(),
 
         // Server
-        // cargo build --example async_http3_server
-        server_binary: "deps/quiche/target/debug/examples/async_http3_server".to_string(),
-        server_ip,
-        server_port: "9999".to_string(),
-        server_cca: args.cc_algorithm,
+        servers,
+        flows,
+        keylog_dir: args.capture.then(|| report::dir(&uuid)),
+    };
+
+    let netem = Netem {
+        loss: args.loss,
+        jitter: args.jitter,
+        reorder: args.reorder,
+        duplicate: args.duplicate,
+        corrupt: args.corrupt,
+    };
+
+    let reverse = Conditions {
+        delay_ms: args.reverse_delay_ms.unwrap_or(args.delay_ms),
+        netem: Netem {
+            loss: args.reverse_loss.unwrap_or(netem.loss.clone()),
+            ..netem.clone()
+        },
+        rate_mbit: args.reverse_rate_mbit.unwrap_or(args.rate_mbit),
+        queue: args.reverse_queue.unwrap_or(args.queue.clone()),
+        buffer: args.reverse_buffer.unwrap_or(args.buffer),
+        // only the download is policed
+        police_burst: None,
+    };
+    let forward = Conditions {
+        delay_ms: args.delay_ms,
+        netem,
+        rate_mbit: args.rate_mbit,
+        queue: args.queue,
+        buffer: args.buffer,
+        police_burst: args.police,
     };
 
     let download_bytes = Byte::parse_str(args.download_size, true).unwrap();
 
-    ExecutionPlan {
-        uuid: Uuid::new_v4(),
-        network: NetworkSetup::new(network_setup),
+    Ok(ExecutionPlan {
+        uuid,
+        network: NetworkSetup::new(topology, forward, reverse)
+            .with_hops(hops)
+            .with_trace(trace)
+            .with_outages(args.outage)
+            .with_nat(nat)
+            .with_path_changes(args.path_change)
+            .with_mtus(mtus, packet_too_big_dropped)
+            .with_offload(offload)
+            .with_sysctls(sysctls),
         endpoint: run_setup,
 
         download_bytes,
         run_count: args.run_count,
+        preflight_tolerance_pct: (!args.skip_preflight).then_some(args.preflight_tolerance_pct),
+        capture: args.capture,
+        rootless: args.rootless,
+        profile,
+    })
+}
+
+/// Fill in the flags `from_command_line` does not report with `profile`.
+fn apply_profile(args: &mut Args, profile: &Profile, from_command_line: impl Fn(&str) -> bool) {
+    macro_rules! apply {
+        ($($field:ident),*) => {
+            $(
+                if let Some(value) = &profile.$field
+                    && !from_command_line(stringify!($field))
+                {
+                    args.$field = value.clone().into();
+                }
+            )*
+        };
     }
+
+    apply!(
+        delay_ms,
+        reverse_delay_ms,
+        loss,
+        reverse_loss,
+        jitter,
+        rate_mbit,
+        reverse_rate_mbit,
+        queue,
+        buffer,
+        reverse_queue,
+        reverse_buffer
+    );
 }
@@ -1,44 +1,123 @@
+use crate::interrupt;
 use byte_unit::Byte;
 use std::{
     fmt::Debug,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     process::{Child, Command, Stdio},
     sync::{Arc, Mutex},
     thread,
+    time::Duration,
 };
 
 #[derive(Debug, Clone)]
 pub struct EndpointSetup {
     pub client_binary: String,
     pub client_logging: String,
-    pub server_binary: String,
-    pub server_ip: String,
-    pub server_port: String,
-    pub server_cca: String,
+    /// One server process per congestion control algorithm, spread over the
+    /// server namespaces.
+    pub servers: Vec<ServerSetup>,
+    /// Downloads run concurrently in every client run.
+    pub flows: Vec<Flow>,
+    /// Directory the TLS keys of the clients and servers are logged to, so
+    /// that captures can be decrypted.
+    pub keylog_dir: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerSetup {
+    pub binary: String,
+    pub ns: String,
+    /// The address clients connect to.
+    pub ip: IpAddr,
+    pub port: u16,
+    pub cca: String,
+}
+
+impl ServerSetup {
+    /// Tag of the results of this server in the report.
+    pub fn tag(&self) -> String {
+        format!("{}_{}_{}", self.ns, self.cca, self.port)
+    }
+}
+
+/// How often a running client checks for an interruption.
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A running server, killed when dropped.
+#[must_use = "the server is killed when the guard is dropped"]
+pub struct Server {
+    child: Child,
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    /// Log lines written so far.
+    pub fn logs(&self) -> Vec<String> {
+        self.logs.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// One download of a client run.
+#[derive(Debug, Clone)]
+pub struct Flow {
+    pub client_ns: String,
+    /// Index into `EndpointSetup::servers`.
+    pub server: usize,
+    /// Delay from the start of the run until this download starts.
+    pub start_offset: Duration,
 }
 
 impl EndpointSetup {
-    pub fn run_server(&self) -> (Child, Arc<Mutex<Vec<String>>>) {
-        let server = &self.server_binary;
+    /// Whether some flows run from the same client namespace, so that the
+    /// bytes they receive cannot be told apart.
+    pub fn flows_share_clients(&self) -> bool {
+        self.flows.iter().enumerate().any(|(i, flow)| {
+            self.flows[..i]
+                .iter()
+                .any(|other| other.client_ns == flow.client_ns)
+        })
+    }
+
+    pub fn run_servers(&self) -> Vec<Server> {
+        self.servers
+            .iter()
+            .map(|server| self.run_server(server))
+            .collect()
+    }
+
+    fn run_server(&self, setup: &ServerSetup) -> Server {
+        let server = &setup.binary;
+        // listen on the address family the clients connect over
+        let unspecified: IpAddr = if setup.ip.is_ipv6() {
+            Ipv6Addr::UNSPECIFIED.into()
+        } else {
+            Ipv4Addr::UNSPECIFIED.into()
+        };
+        let address = SocketAddr::new(unspecified, setup.port);
+        // exec so that killing the child kills the server itself
         let server = format!(
-            "{} {:?} --address 0.0.0.0:{}  --cc-algorithm {}",
-            self.client_logging, server, self.server_port, self.server_cca
+            "{} exec {:?} --address {}  --cc-algorithm {}",
+            self.client_logging, server, address, setup.cca
         );
 
-        cfg_if::cfg_if! {
-            if #[cfg(target_os = "linux")] {
-                let mut cmd = Command::new("ip");
-                let cmd = cmd.args(["netns", "exec", "ns_s1"]);
-
-                let cmd = cmd.args(["sh", "-c"]);
-            } else {
-                let mut cmd = Command::new("sh");
-                let cmd = cmd.arg("-c");
-            }
-        }
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        #[cfg(target_os = "linux")]
+        crate::network::netlink::exec_in_netns(&mut cmd, &setup.ns).expect("server namespace");
 
         cmd.arg(&server).stdout(Stdio::piped());
         cmd.arg(&server).stderr(Stdio::piped());
+        if let Some(dir) = &self.keylog_dir {
+            cmd.env("SSLKEYLOGFILE", format!("{dir}/sslkeylog_server.txt"));
+        }
         // dbg!("{:?}", &cmd);
 
         // cmd.status().unwrap();
@@ -51,6 +130,8 @@ impl EndpointSetup {
             // let server_logs = server_logs.clone();
             // thread::sleep(Duration::from_millis(1));
             let reader = BufReader::new(stdout);
+            // a line which is not UTF-8 is skipped, it does not end the logs
+            #[allow(clippy::lines_filter_map_ok)]
             reader
                 .lines()
                 .filter_map(|line| line.ok())
@@ -60,36 +141,78 @@ impl EndpointSetup {
                 });
         });
 
-        (server, server_logs)
+        Server {
+            child: server,
+            logs: server_logs,
+        }
     }
 
-    pub fn run_client(&self, download_bytes: &Byte) -> String {
+    /// Run every flow, each starting after its offset, and return the client
+    /// logs in flow order. Clients are killed early on an interruption.
+    pub fn run_clients(&self, download_bytes: &Byte) -> Vec<String> {
+        thread::scope(|scope| {
+            let clients: Vec<_> = self
+                .flows
+                .iter()
+                .map(|flow| {
+                    scope.spawn(move || {
+                        thread::sleep(flow.start_offset);
+                        if interrupt::interrupted() {
+                            return String::new();
+                        }
+                        self.run_client(flow, download_bytes)
+                    })
+                })
+                .collect();
+
+            clients
+                .into_iter()
+                .map(|client| client.join().expect("client thread panicked"))
+                .collect()
+        })
+    }
+
+    fn run_client(&self, flow: &Flow, download_bytes: &Byte) -> String {
         let client = &self.client_binary;
+        let server = &self.servers[flow.server];
 
         // let download_bytes = Byte::parse_str(plan.download_payload_size, true).unwrap();
+        // brackets around an IPv6 address
+        let connect_to = SocketAddr::new(server.ip, server.port);
         let client = format!(
-            "{} {} https://test.com/stream-bytes/{} --no-verify --connect-to  {}:{} --idle-timeout 5",
-            self.client_logging, client, download_bytes, self.server_ip, self.server_port
+            "{} exec {} https://test.com/stream-bytes/{} --no-verify --connect-to  {} --idle-timeout 5",
+            self.client_logging, client, download_bytes, connect_to
         );
 
-        cfg_if::cfg_if! {
-            if #[cfg(target_os = "linux")] {
-                let mut cmd = Command::new("ip");
-                let cmd = cmd.args(["netns", "exec", "ns_c1"]);
-
-                let cmd = cmd.args(["sh", "-c"]);
-            } else {
-                let mut cmd = Command::new("sh");
-                let cmd = cmd.arg("-c");
-            }
-        }
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        #[cfg(target_os = "linux")]
+        crate::network::netlink::exec_in_netns(&mut cmd, &flow.client_ns)
+            .expect("client namespace");
 
         cmd.arg(client).stderr(Stdio::piped());
+        if let Some(dir) = &self.keylog_dir {
+            cmd.env("SSLKEYLOGFILE", format!("{dir}/sslkeylog_client.txt"));
+        }
         // dbg!("client cmd ---: {:?}", &cmd);
 
-        let res = cmd.output().unwrap();
-        let logs = String::from_utf8(res.stderr).unwrap();
+        let mut client = cmd.spawn().unwrap();
+        let mut stderr = client.stderr.take().unwrap();
+        let logs = thread::spawn(move || {
+            let mut logs = String::new();
+            stderr.read_to_string(&mut logs).unwrap();
+            logs
+        });
+
+        while client.try_wait().unwrap().is_none() {
+            if interrupt::interrupted() {
+                let _ = client.kill();
+                let _ = client.wait();
+                break;
+            }
+            thread::sleep(CLIENT_POLL_INTERVAL);
+        }
 
-        logs
+        logs.join().expect("client log thread panicked")
     }
 }
@@ -1,7 +1,219 @@
+use crate::network::topology::{IfaceAddr, Route};
+use std::io;
+
 pub type Result<T, E = BoarError> = core::result::Result<T, E>;
 
 #[derive(Debug)]
 pub enum BoarError {
     #[allow(dead_code)]
     Script(String),
+    #[allow(dead_code)]
+    Topology(TopologyError),
+    #[allow(dead_code)]
+    Signal(ctrlc::Error),
+    #[allow(dead_code)]
+    Preflight(PreflightError),
+    #[allow(dead_code)]
+    Profile(ProfileError),
+    #[allow(dead_code)]
+    Trace(TraceError),
+    /// Entering the user namespace of `--rootless` failed at `step`.
+    #[allow(dead_code)]
+    Rootless {
+        step: &'static str,
+        source: io::Error,
+    },
+    /// tcpdump failed to start on `link`.
+    #[allow(dead_code)]
+    Capture {
+        ns: String,
+        link: String,
+        stderr: String,
+    },
+}
+
+impl From<TopologyError> for BoarError {
+    fn from(err: TopologyError) -> Self {
+        BoarError::Topology(err)
+    }
+}
+
+impl From<PreflightError> for BoarError {
+    fn from(err: PreflightError) -> Self {
+        BoarError::Preflight(err)
+    }
+}
+
+impl From<ProfileError> for BoarError {
+    fn from(err: ProfileError) -> Self {
+        BoarError::Profile(err)
+    }
+}
+
+impl From<TraceError> for BoarError {
+    fn from(err: TraceError) -> Self {
+        BoarError::Trace(err)
+    }
+}
+
+/// An invalid topology description, or a failed step while building or
+/// tearing down the emulated network.
+///
+/// Every variant names the file, namespace or object which failed so that a
+/// half-built topology can be debugged without re-running the setup.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum TopologyError {
+    Read {
+        file: String,
+        source: io::Error,
+    },
+    Parse {
+        file: String,
+        source: toml::de::Error,
+    },
+    Invalid {
+        file: String,
+        reason: String,
+    },
+    AddNamespace {
+        ns: String,
+        source: io::Error,
+    },
+    DeleteNamespace {
+        ns: String,
+        source: io::Error,
+    },
+    EnterNamespace {
+        ns: String,
+        source: io::Error,
+    },
+    Connect {
+        ns: String,
+        source: io::Error,
+    },
+    AddVeth {
+        link: String,
+        peer: String,
+        source: io::Error,
+    },
+    DeleteLink {
+        link: String,
+        source: io::Error,
+    },
+    LinkNotFound {
+        ns: String,
+        link: String,
+    },
+    MoveLink {
+        link: String,
+        ns: String,
+        source: io::Error,
+    },
+    LinkUp {
+        ns: String,
+        link: String,
+        source: io::Error,
+    },
+    SetMtu {
+        ns: String,
+        link: String,
+        mtu: u32,
+        source: io::Error,
+    },
+    AddAddress {
+        ns: String,
+        link: String,
+        addr: IfaceAddr,
+        source: io::Error,
+    },
+    AddRoute {
+        ns: String,
+        route: Route,
+        source: io::Error,
+    },
+    Sysctl {
+        ns: String,
+        key: String,
+        source: io::Error,
+    },
+    Offload {
+        ns: String,
+        link: String,
+        stderr: String,
+    },
+    /// Another run holds the host-wide sysctls, `source` being set when the
+    /// lock file could not be locked at all.
+    HostSysctlsInUse {
+        lock_file: String,
+        source: Option<io::Error>,
+    },
+    /// The kernel kept an offload feature of the interface as it was.
+    OffloadNotApplied {
+        ns: String,
+        link: String,
+        feature: String,
+    },
+    Qdisc {
+        ns: String,
+        link: String,
+        stderr: String,
+    },
+    /// nft or conntrack failed on the NAT node.
+    Nat {
+        ns: String,
+        stderr: String,
+    },
+    /// nft failed to drop ICMP packet too big on a router.
+    PacketTooBig {
+        ns: String,
+        stderr: String,
+    },
+}
+
+/// A network profile file which could not be read.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum ProfileError {
+    Read {
+        file: String,
+        source: io::Error,
+    },
+    Parse {
+        file: String,
+        source: toml::de::Error,
+    },
+}
+
+/// A bandwidth/delay trace file which could not be read or replayed.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum TraceError {
+    Read { file: String, source: io::Error },
+    Invalid { file: String, reason: String },
+}
+
+/// The emulated network does not match the plan, found by the pre-flight
+/// probes before any client run.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum PreflightError {
+    Socket {
+        ns: String,
+        source: io::Error,
+    },
+    /// Every RTT probe or the whole throughput burst was lost.
+    NoReply {
+        ns: String,
+    },
+    Rtt {
+        expected_ms: u64,
+        measured_ms: f64,
+        tolerance_pct: f64,
+    },
+    Rate {
+        expected_mbit: u64,
+        measured_mbit: f64,
+        tolerance_pct: f64,
+    },
 }
@@ -0,0 +1,22 @@
+use crate::error::{BoarError, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Record SIGINT, SIGTERM and SIGHUP instead of exiting, so that the running
+/// client is stopped, the network and servers are torn down by their guards
+/// and a report is written for the runs which completed.
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            eprintln!("Interrupted again, still tearing down...");
+        } else {
+            eprintln!("Interrupted, stopping after tearing down the network...");
+        }
+    })
+    .map_err(BoarError::Signal)
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
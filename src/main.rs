@@ -1,10 +1,21 @@
 use crate::{
     endpoint::EndpointSetup,
     error::Result,
-    network::NetworkSetup,
+    network::{NetworkSetup, profile::Profile},
     stats::{
-        Stats, delivery_rate::DeliveryRate, download_duration::DownloadDuration,
+        Stats,
+        delivery_rate::DeliveryRate,
+        download_completion::DownloadCompletion,
+        download_duration::DownloadDuration,
+        ecn_marks::EcnMarks,
+        flow_rate,
+        jain_fairness::JainFairness,
+        path_change_stall::PathChangeStall,
+        pmtu::{ClientPmtu, ServerPmtu},
+        qdisc::{Policing, QdiscDrops, QdiscMaxBacklog, QdiscOverlimits, QdiscRequeues},
+        recovery_time::RecoveryTime,
         startup_exit::StartupExit,
+        throughput_share::ThroughputShare,
     },
 };
 use byte_unit::Byte;
@@ -14,6 +25,7 @@ use uuid::Uuid;
 mod args;
 mod endpoint;
 mod error;
+mod interrupt;
 mod network;
 mod report;
 mod stats;
@@ -25,42 +37,251 @@ struct ExecutionPlan {
     endpoint: EndpointSetup,
     download_bytes: Byte,
     run_count: u16,
+    /// None to skip the pre-flight check.
+    preflight_tolerance_pct: Option<f64>,
+    /// Capture pcaps of every run into the report directory.
+    capture: bool,
+    /// Run in a user namespace instead of as root.
+    rootless: bool,
+    /// The profile the network conditions were taken from, as defined,
+    /// recorded in the report.
+    #[allow(dead_code)]
+    profile: Option<Profile>,
 }
 
 fn main() -> Result<()> {
     // Cli
-    let plan = args::parse();
+    let plan = args::parse()?;
     // dbg!(&setup, &plan);
 
     // println!("Executing: {:#?}", &plan);
 
+    if plan.rootless {
+        // before the signal handler thread is spawned
+        network::enter_rootless()?;
+    }
+    interrupt::install()?;
+
     // Network
-    plan.network.cleanup()?;
-    plan.network.create()?;
+    let _network = plan.network.create()?;
+    let sysctls = plan.network.sysctl_snapshot(!plan.rootless)?;
+    if let Some(tolerance_pct) = plan.preflight_tolerance_pct {
+        plan.network.verify(tolerance_pct)?;
+    }
 
     // Run
-    let (mut server, server_logs) = plan.endpoint.run_server();
+    let capture_namespaces = {
+        let mut namespaces = Vec::new();
+        let servers = plan.endpoint.servers.iter().map(|server| &server.ns);
+        let clients = plan.endpoint.flows.iter().map(|flow| &flow.client_ns);
+        for ns in servers.chain(clients) {
+            if !namespaces.contains(&ns.as_str()) {
+                namespaces.push(ns);
+            }
+        }
+        namespaces
+    };
+    let report_dir = if plan.capture {
+        // the servers log their TLS keys into it from the start
+        report::Report::create_report_dir(&plan)
+    } else {
+        report::dir(&plan.uuid)
+    };
+    let servers = plan.endpoint.run_servers();
 
+    let mut download_completion = Vec::new();
     let mut download_duration = Vec::new();
     let mut delivery_rate = Vec::new();
+    let mut ecn_marks = Vec::new();
+    let mut jain_fairness = Vec::new();
+    let mut throughput_share = Vec::new();
+    let mut qdisc_drops = Vec::new();
+    let mut qdisc_overlimits = Vec::new();
+    let mut qdisc_requeues = Vec::new();
+    let mut qdisc_max_backlog = Vec::new();
+    let mut policing = Vec::new();
+    let mut recovery_time = Vec::new();
+    let mut path_change_stall = Vec::new();
+    let mut client_pmtu = Vec::new();
+    let mut bottlenecks: Vec<(String, u16)> = Vec::new();
+    // per-flow results again, split by server
+    let server_count = plan.endpoint.servers.len();
+    let mut server_download_completion = vec![Vec::new(); server_count];
+    let mut server_download_duration = vec![Vec::new(); server_count];
+    let mut server_delivery_rate = vec![Vec::new(); server_count];
+    let mut server_throughput_share = vec![Vec::new(); server_count];
+    let mut completed_runs = 0;
     for i in 1..=plan.run_count {
-        let client_logs = plan.endpoint.run_client(&plan.download_bytes);
-        let metric_download_duration = DownloadDuration::new_from_logs(&client_logs);
-        println!(
-            "Run [{}/{}]: Download duration: {:?}",
-            i, plan.run_count, metric_download_duration
-        );
-        download_duration.push(Box::new(metric_download_duration));
-        delivery_rate.push(Box::new(DeliveryRate::new_from_logs(&client_logs)));
+        if interrupt::interrupted() {
+            break;
+        }
+
+        let qdisc_stats = EcnMarks::new_from_qdisc_stats(&plan.network.qdisc_stats()?);
+        let capture = plan
+            .capture
+            .then(|| plan.network.capture(&report_dir, i, &capture_namespaces))
+            .transpose()?;
+        let qdiscs = plan.network.monitor_qdiscs()?;
+        let trace = plan.network.replay_trace();
+        let outages = plan.network.schedule_outages();
+        let path_changes = plan.network.schedule_path_changes();
+        let client_logs = plan.endpoint.run_clients(&plan.download_bytes);
+        if let Some(path_changes) = path_changes {
+            path_changes.stop()?;
+        }
+        if let Some(outages) = outages {
+            outages.stop()?;
+        }
+        if let Some(trace) = trace {
+            trace.stop()?;
+        }
+        let qdisc_run = qdiscs.stop()?;
+        drop(capture);
+        if interrupt::interrupted() {
+            // the clients were killed, so their logs are incomplete
+            break;
+        }
+        let metric_ecn_marks =
+            EcnMarks::new_from_qdisc_stats(&plan.network.qdisc_stats()?).since(&qdisc_stats);
+
+        let mut goodputs = Vec::new();
+        let mut first_client_completed = true;
+        for (flow, client_logs) in client_logs.iter().enumerate() {
+            let server = plan.endpoint.flows[flow].server;
+            if let Some(metric_pmtu) = ClientPmtu::new_from_logs(client_logs) {
+                println!(
+                    "Run [{}/{}] flow {}: PMTU: {:?}",
+                    i, plan.run_count, flow, metric_pmtu
+                );
+                client_pmtu.push(Box::new(metric_pmtu));
+            }
+            let metric_download_completion = DownloadCompletion::new_from_logs(client_logs);
+            download_completion.push(Box::new(metric_download_completion));
+            server_download_completion[server].push(Box::new(metric_download_completion));
+            if !metric_download_completion.completed {
+                println!(
+                    "Run [{}/{}] flow {}: Download did not complete",
+                    i, plan.run_count, flow
+                );
+                if plan.endpoint.flows[flow].client_ns == plan.endpoint.flows[0].client_ns {
+                    first_client_completed = false;
+                }
+                goodputs.push(0.0);
+                continue;
+            }
+
+            let metric_download_duration = DownloadDuration::new_from_logs(client_logs);
+            println!(
+                "Run [{}/{}] flow {}: Download duration: {:?}",
+                i, plan.run_count, flow, metric_download_duration
+            );
+            goodputs.push(metric_download_duration.goodput(&plan.download_bytes));
+            let metric_delivery_rate = DeliveryRate::new_from_logs(client_logs);
+            server_download_duration[server].push(Box::new(metric_download_duration.clone()));
+            server_delivery_rate[server].push(Box::new(metric_delivery_rate.clone()));
+            download_duration.push(Box::new(metric_download_duration));
+            delivery_rate.push(Box::new(metric_delivery_rate));
+        }
+        ecn_marks.push(Box::new(metric_ecn_marks));
+        // towards the first client, which policing, outages and path changes
+        // are measured at
+        let first_client = &plan.endpoint.flows[0].client_ns;
+        let delivered = qdisc_run.delivered(first_client);
+        if let Some(hop) = qdisc_run.bottleneck() {
+            println!("Run [{}/{}] bottleneck: {}", i, plan.run_count, hop);
+            match bottlenecks.iter_mut().find(|(name, _)| name == hop) {
+                Some((_, runs)) => *runs += 1,
+                None => bottlenecks.push((hop.to_string(), 1)),
+            }
+        }
+        let metric_policing = qdisc_run.policing(first_client);
+        if let Some(hop) = metric_policing.hop() {
+            println!(
+                "Run [{}/{}] policing detected at {}",
+                i, plan.run_count, hop
+            );
+        }
+        policing.push(Box::new(metric_policing));
+        for (outage, window) in plan
+            .network
+            .outages()
+            .iter()
+            .map(|o| o.window())
+            .enumerate()
+        {
+            let link_bps = (plan.network.forward_rate_mbit() * 1_000_000) as f64;
+            match RecoveryTime::new_from_delivered(delivered, outage, window, link_bps) {
+                Some(metric) => recovery_time.push(Box::new(metric)),
+                None => println!(
+                    "Run [{}/{}] outage {}: goodput did not recover before the run ended",
+                    i, plan.run_count, outage
+                ),
+            }
+        }
+        for (change, path_change) in plan.network.path_changes().iter().enumerate() {
+            path_change_stall.push(Box::new(PathChangeStall::new_from_delivered(
+                delivered,
+                change,
+                path_change.at,
+                first_client_completed,
+            )));
+        }
+        // with a client namespace per flow, the flows are compared while
+        // they all ran, not over downloads which started at other times
+        let goodputs = if plan.endpoint.flows.len() == 1 || plan.endpoint.flows_share_clients() {
+            Some(goodputs)
+        } else {
+            let delivered: Vec<_> = plan
+                .endpoint
+                .flows
+                .iter()
+                .map(|flow| qdisc_run.delivered(&flow.client_ns))
+                .collect();
+            flow_rate::while_all_active(&delivered)
+        };
+        match goodputs {
+            Some(goodputs) => {
+                jain_fairness.push(Box::new(JainFairness::new_from_goodputs(&goodputs)));
+                for (flow, metric) in ThroughputShare::new_from_goodputs(&goodputs)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let server = plan.endpoint.flows[flow].server;
+                    server_throughput_share[server].push(Box::new(metric.clone()));
+                    throughput_share.push(Box::new(metric));
+                }
+            }
+            None => println!(
+                "Run [{}/{}]: the flows never ran all at once, no fairness recorded",
+                i, plan.run_count
+            ),
+        }
+        qdisc_drops.push(Box::new(qdisc_run.drops));
+        qdisc_overlimits.push(Box::new(qdisc_run.overlimits));
+        qdisc_requeues.push(Box::new(qdisc_run.requeues));
+        qdisc_max_backlog.push(Box::new(qdisc_run.max_backlog));
+        completed_runs += 1;
     }
 
-    let server_logs = server_logs.lock().unwrap().clone();
-    let startup_exit = StartupExit::new_from_logs(&server_logs);
+    let server_logs: Vec<_> = servers.iter().map(|server| server.logs()).collect();
+    drop(servers);
+
+    if completed_runs == 0 {
+        println!("Interrupted before any run completed, no report written");
+        return Ok(());
+    }
 
+    let startup_exit = StartupExit::new_from_logs(&server_logs.concat());
+    let server_pmtu = ServerPmtu::new_from_logs(&server_logs.concat());
+
+    let download_completion = Stats::new::<DownloadCompletion>(
+        download_completion.into_iter().map(|ty| ty as _).collect(),
+    );
     let download_duration =
         Stats::new::<DownloadDuration>(download_duration.into_iter().map(|ty| ty as _).collect());
     let deliver_rate =
         Stats::new::<DeliveryRate>(delivery_rate.into_iter().map(|ty| ty as _).collect());
+    let ecn_marks = Stats::new::<EcnMarks>(ecn_marks.into_iter().map(|ty| ty as _).collect());
     let startup_exit = Stats::new::<StartupExit>(
         startup_exit
             .into_iter()
@@ -68,10 +289,108 @@ fn main() -> Result<()> {
             .collect(),
     );
 
-    server.kill().unwrap();
+    let client_pmtu = Stats::new::<ClientPmtu>(client_pmtu.into_iter().map(|ty| ty as _).collect());
+    let server_pmtu = Stats::new::<ServerPmtu>(
+        server_pmtu
+            .into_iter()
+            .map(|ty| Box::new(ty) as _)
+            .collect(),
+    );
+
+    let qdisc_drops = Stats::new::<QdiscDrops>(qdisc_drops.into_iter().map(|ty| ty as _).collect());
+    let qdisc_overlimits =
+        Stats::new::<QdiscOverlimits>(qdisc_overlimits.into_iter().map(|ty| ty as _).collect());
+    let qdisc_requeues =
+        Stats::new::<QdiscRequeues>(qdisc_requeues.into_iter().map(|ty| ty as _).collect());
+    let qdisc_max_backlog =
+        Stats::new::<QdiscMaxBacklog>(qdisc_max_backlog.into_iter().map(|ty| ty as _).collect());
+    let policing = Stats::new::<Policing>(policing.into_iter().map(|ty| ty as _).collect());
+
+    let mut stats = vec![
+        download_completion,
+        download_duration,
+        deliver_rate,
+        ecn_marks,
+        startup_exit,
+        client_pmtu,
+        server_pmtu,
+        qdisc_drops,
+        qdisc_overlimits,
+        qdisc_requeues,
+        qdisc_max_backlog,
+        policing,
+    ];
+    if !plan.network.path_changes().is_empty() {
+        stats.push(Stats::new::<PathChangeStall>(
+            path_change_stall.into_iter().map(|ty| ty as _).collect(),
+        ));
+    }
+    if !recovery_time.is_empty() {
+        stats.push(Stats::new::<RecoveryTime>(
+            recovery_time.into_iter().map(|ty| ty as _).collect(),
+        ));
+    }
+    if plan.endpoint.flows.len() > 1 {
+        stats.push(Stats::new::<JainFairness>(
+            jain_fairness.into_iter().map(|ty| ty as _).collect(),
+        ));
+        stats.push(Stats::new::<ThroughputShare>(
+            throughput_share.into_iter().map(|ty| ty as _).collect(),
+        ));
+    }
+    if plan.endpoint.servers.len() > 1 {
+        let per_server = plan
+            .endpoint
+            .servers
+            .iter()
+            .zip(server_logs)
+            .zip(server_download_completion)
+            .zip(server_download_duration)
+            .zip(server_delivery_rate)
+            .zip(server_throughput_share);
+        for (((((server, logs), completion), duration), rate), share) in per_server {
+            let tag = server.tag();
+            stats.push(
+                Stats::new::<DownloadCompletion>(
+                    completion.into_iter().map(|ty| ty as _).collect(),
+                )
+                .tagged(tag.clone()),
+            );
+            stats.push(
+                Stats::new::<DownloadDuration>(duration.into_iter().map(|ty| ty as _).collect())
+                    .tagged(tag.clone()),
+            );
+            stats.push(
+                Stats::new::<DeliveryRate>(rate.into_iter().map(|ty| ty as _).collect())
+                    .tagged(tag.clone()),
+            );
+            stats.push(
+                Stats::new::<StartupExit>(
+                    StartupExit::new_from_logs(&logs)
+                        .into_iter()
+                        .map(|ty| Box::new(ty) as _)
+                        .collect(),
+                )
+                .tagged(tag.clone()),
+            );
+            stats.push(
+                Stats::new::<ServerPmtu>(
+                    ServerPmtu::new_from_logs(&logs)
+                        .into_iter()
+                        .map(|ty| Box::new(ty) as _)
+                        .collect(),
+                )
+                .tagged(tag.clone()),
+            );
+            stats.push(
+                Stats::new::<ThroughputShare>(share.into_iter().map(|ty| ty as _).collect())
+                    .tagged(tag),
+            );
+        }
+    }
 
     // Report
-    let report = report::Report::new(&plan, vec![download_duration, deliver_rate, startup_exit]);
+    let report = report::Report::new(&plan, completed_runs, bottlenecks, sysctls, stats);
 
     println!("{:#?}", report);
 
@@ -0,0 +1,108 @@
+use crate::error::{BoarError, Result};
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use nix::{
+            sys::signal::{self, Signal},
+            unistd::Pid,
+        };
+    }
+}
+
+/// tcpdump on a set of interfaces for the duration of a client run, stopped
+/// when dropped.
+#[must_use = "the capture stops when dropped"]
+pub struct Capture {
+    /// (namespace, dev, tcpdump)
+    tcpdumps: Vec<(String, String, Child)>,
+}
+
+impl Capture {
+    /// Capture every (namespace, dev, pcap file) in `ifaces`, returning once
+    /// all of them are listening.
+    pub(crate) fn start(ifaces: &[(String, String, String)]) -> Result<Self> {
+        let mut capture = Capture {
+            tcpdumps: Vec::new(),
+        };
+        for (ns, dev, file) in ifaces {
+            let tcpdump = tcpdump(ns, dev, file)?;
+            capture.tcpdumps.push((ns.clone(), dev.clone(), tcpdump));
+        }
+
+        Ok(capture)
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        for (ns, dev, tcpdump) in &mut self.tcpdumps {
+            cfg_if::cfg_if! {
+                if #[cfg(target_os = "linux")] {
+                    // SIGINT so that tcpdump flushes the pcap
+                    let pid = Pid::from_raw(tcpdump.id() as i32);
+                    if let Err(err) = signal::kill(pid, Signal::SIGINT) {
+                        eprintln!("Failed to stop tcpdump on {ns}/{dev}, its pcap may be truncated: {err}");
+                        let _ = tcpdump.kill();
+                    }
+                } else {
+                    // No network emulation on macOS
+                    let _ = tcpdump.kill();
+                }
+            }
+            match tcpdump.wait() {
+                Ok(status) if !status.success() => {
+                    eprintln!(
+                        "tcpdump on {ns}/{dev} exited with {status}, its pcap may be truncated"
+                    );
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("Failed to wait for tcpdump on {ns}/{dev}: {err}"),
+            }
+        }
+    }
+}
+
+fn tcpdump(ns: &str, dev: &str, file: &str) -> Result<Child> {
+    let err = |stderr: String| BoarError::Capture {
+        ns: ns.to_string(),
+        link: dev.to_string(),
+        stderr,
+    };
+
+    // packet-buffered, full packets, and staying root as the tcpdump user
+    // is not mapped in the user namespace of `--rootless`
+    let mut cmd = Command::new("tcpdump");
+    cmd.args(["-i", dev, "-w", file, "-U", "-s", "0", "-Z", "root"])
+        .stderr(Stdio::piped());
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            let res = crate::network::netlink::exec_in_netns(&mut cmd, ns);
+        } else {
+            // No network emulation on macOS
+            let res = std::io::Result::Ok(());
+        }
+    }
+    let mut tcpdump = res
+        .and_then(|()| cmd.spawn())
+        .map_err(|io| err(io.to_string()))?;
+
+    // tcpdump misses packets until it prints "listening on <dev>"
+    let stderr = tcpdump.stderr.take().expect("piped stderr");
+    let mut lines = BufReader::new(stderr).lines().map_while(|line| line.ok());
+    let mut output = Vec::new();
+    for line in lines.by_ref() {
+        if line.contains("listening on") {
+            // keep draining so that tcpdump never blocks on a full pipe
+            std::thread::spawn(move || lines.for_each(drop));
+            return Ok(tcpdump);
+        }
+        output.push(line);
+    }
+
+    let _ = tcpdump.wait();
+    Err(err(output.join("\n")))
+}
@@ -0,0 +1,140 @@
+use crate::network::{
+    Conditions,
+    netem::{Jitter, LossModel},
+    queue::{BufferSize, Queue},
+};
+use std::str::FromStr;
+
+/// Conditions of the forward qdiscs of one router, overriding those of the
+/// whole forward path on that hop.
+///
+/// `NODE:KEY=VALUE[,KEY=VALUE...]` with the keys `delay_ms`, `loss`,
+/// `jitter`, `rate_mbit`, `queue`, `buffer` and `police`, whose values take
+/// the syntax of the flags of the same name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HopConditions {
+    pub node: String,
+    /// Applied by the netem qdisc of the hop.
+    pub delay_ms: Option<u64>,
+    pub loss: Option<LossModel>,
+    pub jitter: Option<Jitter>,
+    /// Applied by the htb shaper of the hop.
+    pub rate_mbit: Option<u64>,
+    pub queue: Option<Queue>,
+    pub buffer: Option<BufferSize>,
+    /// Burst of a policer replacing the htb shaper of the hop.
+    pub police: Option<BufferSize>,
+}
+
+impl HopConditions {
+    /// Whether the hop overrides anything applied by netem.
+    pub fn sets_netem(&self) -> bool {
+        self.delay_ms.is_some() || self.loss.is_some() || self.jitter.is_some()
+    }
+
+    /// Whether the hop overrides anything applied by htb.
+    pub fn sets_htb(&self) -> bool {
+        self.rate_mbit.is_some()
+            || self.queue.is_some()
+            || self.buffer.is_some()
+            || self.police.is_some()
+    }
+
+    /// `conditions` with the overrides of this hop.
+    pub fn apply(&self, conditions: &Conditions) -> Conditions {
+        let mut conditions = conditions.clone();
+        if let Some(delay_ms) = self.delay_ms {
+            conditions.delay_ms = delay_ms;
+        }
+        if let Some(loss) = &self.loss {
+            conditions.netem.loss = loss.clone();
+        }
+        if let Some(jitter) = self.jitter {
+            conditions.netem.jitter = Some(jitter);
+        }
+        if let Some(rate_mbit) = self.rate_mbit {
+            conditions.rate_mbit = rate_mbit;
+        }
+        if let Some(queue) = &self.queue {
+            conditions.queue = queue.clone();
+        }
+        if let Some(buffer) = self.buffer {
+            conditions.buffer = buffer;
+        }
+        if let Some(burst) = self.police {
+            conditions.police_burst = Some(burst);
+        }
+
+        conditions
+    }
+}
+
+impl FromStr for HopConditions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (node, settings) = s
+            .split_once(':')
+            .ok_or_else(|| format!("{s}: expected NODE:KEY=VALUE[,KEY=VALUE...]"))?;
+
+        let mut hop = HopConditions {
+            node: node.to_string(),
+            ..Default::default()
+        };
+        for setting in settings.split(',') {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("{s}: expected KEY=VALUE, got {setting:?}"))?;
+            let err = |err: String| format!("{s}: {key}: {err}");
+            let int = |value: &str| value.parse::<u64>().map_err(|e| err(e.to_string()));
+            match key {
+                "delay_ms" => hop.delay_ms = Some(int(value)?),
+                "loss" => hop.loss = Some(value.parse().map_err(err)?),
+                "jitter" => hop.jitter = Some(value.parse().map_err(err)?),
+                "rate_mbit" => hop.rate_mbit = Some(int(value)?),
+                "queue" => hop.queue = Some(value.parse().map_err(err)?),
+                "buffer" => hop.buffer = Some(value.parse().map_err(err)?),
+                "police" => hop.police = Some(value.parse().map_err(err)?),
+                _ => {
+                    return Err(format!(
+                        "{s}: unknown key {key:?}, expected delay_ms, loss, jitter, rate_mbit, queue, buffer or police"
+                    ));
+                }
+            }
+        }
+
+        Ok(hop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hop_conditions() {
+        let hop: HopConditions = "ns_m2:rate_mbit=5,delay_ms=10,buffer=0.5bdp"
+            .parse()
+            .unwrap();
+        assert_eq!(hop.node, "ns_m2");
+        assert_eq!(
+            (hop.rate_mbit, hop.delay_ms, hop.buffer),
+            (Some(5), Some(10), Some(BufferSize::Bdp(0.5)))
+        );
+        assert!(hop.sets_htb() && hop.sets_netem());
+
+        let hop: HopConditions = "ns_m1:queue=fq_codel target 2ms,loss=random:1:25"
+            .parse()
+            .unwrap();
+        assert!(hop.queue.is_some() && hop.loss.is_some());
+        assert!(hop.delay_ms.is_none());
+
+        let hop: HopConditions = "ns_m3:police=64kb".parse().unwrap();
+        assert_eq!(hop.police, Some(BufferSize::Bytes(64_000)));
+        assert!(hop.sets_htb() && !hop.sets_netem());
+
+        assert!("ns_m1".parse::<HopConditions>().is_err());
+        assert!("ns_m1:rate=5".parse::<HopConditions>().is_err());
+        assert!("ns_m1:rate_mbit=fast".parse::<HopConditions>().is_err());
+    }
+}
@@ -0,0 +1,762 @@
+use crate::{
+    error::Result,
+    network::{
+        capture::Capture,
+        hop::HopConditions,
+        monitor::HopCounters,
+        nat::{MigratingClient, Nat, PathChange, PathChangeKind, PathChangeTargets},
+        netem::Netem,
+        offload::Offload,
+        outage::{Outage, OutageTarget},
+        pmtu::LinkMtu,
+        queue::{BufferSize, PACKET_BYTES, Queue},
+        sysctl::{SysctlProfile, SysctlSnapshot},
+        topology::{Iface, Qdisc, Topology},
+        trace::{Trace, TraceTarget, TraceTargets},
+        worker::Worker,
+    },
+    stats::qdisc::QdiscRun,
+};
+use std::{fmt::Debug, time::Duration};
+
+pub mod capture;
+pub mod hop;
+pub mod monitor;
+pub mod nat;
+pub mod netem;
+#[cfg(target_os = "linux")]
+pub(crate) mod netlink;
+#[cfg(target_os = "linux")]
+mod nft;
+pub mod offload;
+pub mod outage;
+pub mod pmtu;
+#[cfg(target_os = "linux")]
+pub mod preflight;
+pub mod profile;
+pub mod queue;
+#[cfg(target_os = "linux")]
+mod rootless;
+pub mod sysctl;
+mod tc;
+pub mod topology;
+pub mod trace;
+pub mod worker;
+
+#[derive(Debug, Clone)]
+pub struct NetworkSetup {
+    topology: Topology,
+    /// Server to client, the direction of the download.
+    forward: Conditions,
+    /// Client to server, carrying requests and ACKs.
+    reverse: Conditions,
+    /// Overrides of `forward` on single routers.
+    hops: Vec<HopConditions>,
+    /// Round trip propagation delay between the server and the first client.
+    base_rtt_ms: u64,
+    /// Replayed on the forward htb and netem qdiscs during each client run.
+    trace: Option<Trace>,
+    /// Applied to the netem qdiscs during each client run.
+    outages: Vec<Outage>,
+    nat: Option<Nat>,
+    /// Applied during each client run.
+    path_changes: Vec<PathChange>,
+    /// Links whose MTU differs from the veth default.
+    mtus: Vec<LinkMtu>,
+    /// Router dropping the ICMP packet too big messages of the path.
+    packet_too_big_dropped: Option<String>,
+    offload: Offload,
+    sysctls: SysctlProfile,
+}
+
+/// Continue in a new user namespace in which boar is root, so that the
+/// network can be built without real root. Must be called before any
+/// thread is spawned.
+pub fn enter_rootless() -> Result<()> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            rootless::enter()
+        } else {
+            // No network emulation on macOS
+            Ok(())
+        }
+    }
+}
+
+/// Conditions emulated by the netem and htb qdiscs in one direction.
+#[derive(Debug, Clone)]
+pub struct Conditions {
+    /// One-way delay of each netem qdisc.
+    pub delay_ms: u64,
+    /// Loss, jitter, reordering, duplication and corruption added by netem.
+    pub netem: Netem,
+    /// Rate of each htb shaper.
+    pub rate_mbit: u64,
+    /// Leaf qdisc of each htb shaper.
+    pub queue: Queue,
+    pub buffer: BufferSize,
+    /// Police `rate_mbit` with this burst instead of shaping it, dropping
+    /// what exceeds it rather than queueing it in `queue`.
+    pub police_burst: Option<BufferSize>,
+}
+
+impl Conditions {
+    /// Bandwidth-delay product a `BufferSize::Bdp` is relative to.
+    pub fn bdp_bytes(&self, rtt_ms: u64) -> u64 {
+        // mbit/s * ms = 125 bytes
+        self.rate_mbit * rtt_ms * 125
+    }
+}
+
+impl NetworkSetup {
+    pub fn new(topology: Topology, forward: Conditions, reverse: Conditions) -> Self {
+        let mut network = NetworkSetup {
+            sysctls: SysctlProfile::new(topology.has_ipv6()),
+            topology,
+            forward,
+            reverse,
+            hops: Vec::new(),
+            base_rtt_ms: 0,
+            trace: None,
+            outages: Vec::new(),
+            nat: None,
+            path_changes: Vec::new(),
+            mtus: Vec::new(),
+            packet_too_big_dropped: None,
+            offload: Offload::default(),
+        };
+        network.base_rtt_ms = network.propagation_rtt_ms();
+        network
+    }
+
+    /// The same setup with the conditions of single routers overridden.
+    pub fn with_hops(mut self, hops: Vec<HopConditions>) -> Self {
+        self.hops = hops;
+        self.base_rtt_ms = self.propagation_rtt_ms();
+        self
+    }
+
+    /// The same setup with `trace` replayed during each client run.
+    pub fn with_trace(mut self, trace: Option<Trace>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// The same setup with `outages` during each client run.
+    pub fn with_outages(mut self, outages: Vec<Outage>) -> Self {
+        self.outages = outages;
+        self
+    }
+
+    /// The same setup with a NAT on one router.
+    pub fn with_nat(mut self, nat: Option<Nat>) -> Self {
+        self.nat = nat;
+        self
+    }
+
+    /// The same setup with `path_changes` during each client run.
+    pub fn with_path_changes(mut self, path_changes: Vec<PathChange>) -> Self {
+        self.path_changes = path_changes;
+        self
+    }
+
+    /// The same setup with the MTU of some links lowered or raised, and
+    /// optionally a router which drops ICMP packet too big messages.
+    pub fn with_mtus(mut self, mtus: Vec<LinkMtu>, packet_too_big_dropped: Option<String>) -> Self {
+        self.mtus = mtus;
+        self.packet_too_big_dropped = packet_too_big_dropped;
+        self
+    }
+
+    /// The same setup with other offloads on every interface.
+    pub fn with_offload(mut self, offload: Offload) -> Self {
+        self.offload = offload;
+        self
+    }
+
+    /// The same setup with other sysctls.
+    pub fn with_sysctls(mut self, sysctls: SysctlProfile) -> Self {
+        self.sysctls = sysctls;
+        self
+    }
+
+    /// Sum of the netem delays between the server and the first client, and
+    /// back.
+    fn propagation_rtt_ms(&self) -> u64 {
+        let server = &self.topology.server().expect("validated topology").name;
+        let client = &self
+            .topology
+            .clients()
+            .next()
+            .expect("validated topology")
+            .name;
+        let delay_ms = |from, to| -> u64 {
+            self.topology
+                .egress_path(from, to)
+                .into_iter()
+                .filter(|iface| iface.qdisc.is_some_and(Qdisc::delays))
+                .map(|iface| self.conditions(iface).delay_ms)
+                .sum()
+        };
+        delay_ms(server, client) + delay_ms(client, server)
+    }
+
+    pub fn cleanup(&self) -> Result<()> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                self.topology.cleanup()
+            } else {
+                // No network emulation on macOS
+                Ok(())
+            }
+        }
+    }
+
+    /// Build the network. It is torn down when the returned guard is
+    /// dropped, including when building it fails half way. If one of its
+    /// namespaces exists already nothing is built and nothing is torn down.
+    pub fn create(&self) -> Result<NetworkGuard<'_>> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                // before the guard, which would delete the namespace in the way
+                self.topology.create_namespaces()?;
+                let mut guard = NetworkGuard {
+                    network: self,
+                    host_sysctls: Default::default(),
+                };
+                // restored by the guard, or by `write_host` when it fails
+                guard.host_sysctls = sysctl::write_host(&self.sysctls.host)?;
+
+                self.topology.create()?;
+                for node in &self.topology.nodes {
+                    netlink::in_netns(&node.name, || {
+                        sysctl::write(&node.name, &self.sysctls.namespace)
+                    })?;
+                }
+                offload::set(&self.topology, &self.offload)?;
+                pmtu::set_mtus(&self.topology, &self.mtus)?;
+                if let Some(node) = &self.packet_too_big_dropped {
+                    pmtu::drop_packet_too_big(node)?;
+                }
+                self.create_qdiscs()?;
+                if let Some(nat) = &self.nat {
+                    nat::create(&self.topology, nat)?;
+                }
+                nat::add_migration_addrs(&self.topology, &self.migrating_clients())?;
+
+                Ok(guard)
+            } else {
+                // No network emulation on macOS
+                Ok(NetworkGuard { network: self })
+            }
+        }
+    }
+
+    /// The sysctls of every namespace, and of the host with `host`, once the
+    /// network is built. A rootless run sees the sysctls of its user
+    /// namespace, not those of the host.
+    pub fn sysctl_snapshot(&self, host: bool) -> Result<SysctlSnapshot> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                let namespaces: Vec<_> =
+                    self.topology.nodes.iter().map(|node| node.name.as_str()).collect();
+                sysctl::snapshot(&namespaces, &self.sysctls, host)
+            } else {
+                // No network emulation on macOS
+                Ok(SysctlSnapshot::new())
+            }
+        }
+    }
+
+    /// Check that the RTT and the server to client throughput between the
+    /// server and the first client are within `tolerance_pct` of the plan.
+    pub fn verify(&self, tolerance_pct: f64) -> Result<()> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                use crate::error::PreflightError;
+                use preflight::Endpoint;
+
+                let topology = &self.topology;
+                let server = topology.server().expect("validated topology");
+                let client = topology.clients().next().expect("validated topology");
+                // wait long enough for a full bottleneck buffer to drain
+                let timeout = Duration::from_millis(self.base_rtt_ms * 2) + Duration::from_secs(1);
+
+                let rate_mbit = self.forward_rate_mbit();
+                let measured = preflight::measure(
+                    &Endpoint::new(topology, &server.name),
+                    &Endpoint::new(topology, &client.name),
+                    rate_mbit,
+                    timeout,
+                )?;
+                println!("Pre-flight: {measured:?}");
+
+                let tolerance = tolerance_pct / 100.0;
+                let expected_rtt_ms = self.base_rtt_ms as f64;
+                // veth and scheduling add a little to an RTT of zero
+                if (measured.rtt_ms - expected_rtt_ms).abs() > expected_rtt_ms * tolerance + 2.0 {
+                    return Err(PreflightError::Rtt {
+                        expected_ms: self.base_rtt_ms,
+                        measured_ms: measured.rtt_ms,
+                        tolerance_pct,
+                    }
+                    .into());
+                }
+                let expected_mbit = rate_mbit as f64;
+                if (measured.rate_mbit - expected_mbit).abs() > expected_mbit * tolerance {
+                    return Err(PreflightError::Rate {
+                        expected_mbit: rate_mbit,
+                        measured_mbit: measured.rate_mbit,
+                        tolerance_pct,
+                    }
+                    .into());
+                }
+
+                Ok(())
+            } else {
+                // No network emulation on macOS
+                let _ = tolerance_pct;
+                Ok(())
+            }
+        }
+    }
+
+    /// Start replaying the trace, if any. The returned replay must be stopped
+    /// once the client run completes.
+    pub fn replay_trace(&self) -> Option<Worker<()>> {
+        if cfg!(not(target_os = "linux")) {
+            // No network emulation on macOS
+            return None;
+        }
+
+        let trace = self.trace.clone()?;
+        let forward = |has: fn(Qdisc) -> bool| {
+            self.qdisc_ifaces(has)
+                .filter(|iface| self.topology.is_forward(iface))
+                .map(|iface| TraceTarget {
+                    ns: iface.node.clone(),
+                    dev: iface.name.clone(),
+                    base: self.conditions(iface),
+                })
+                .collect()
+        };
+        let targets = TraceTargets {
+            htb: forward(Qdisc::shapes),
+            netem: forward(Qdisc::delays),
+        };
+        Some(trace::replay(trace, targets))
+    }
+
+    pub fn outages(&self) -> &[Outage] {
+        &self.outages
+    }
+
+    /// Rate of the server to client bottleneck before any trace is applied,
+    /// the slowest htb shaper between the server and the first client.
+    pub fn forward_rate_mbit(&self) -> u64 {
+        let server = &self.topology.server().expect("validated topology").name;
+        let client = &self
+            .topology
+            .clients()
+            .next()
+            .expect("validated topology")
+            .name;
+        self.topology
+            .egress_path(server, client)
+            .into_iter()
+            .filter(|iface| iface.qdisc.is_some_and(Qdisc::shapes))
+            .map(|iface| self.conditions(iface).rate_mbit)
+            .min()
+            .unwrap_or(self.forward.rate_mbit)
+    }
+
+    /// Start the outage schedule, if any. The returned schedule must be
+    /// stopped once the client run completes.
+    pub fn schedule_outages(&self) -> Option<Worker<()>> {
+        if cfg!(not(target_os = "linux")) || self.outages.is_empty() {
+            // No network emulation on macOS
+            return None;
+        }
+
+        let targets = self
+            .qdisc_ifaces(Qdisc::delays)
+            .map(|iface| {
+                let conditions = self.conditions(iface);
+                OutageTarget {
+                    ns: iface.node.clone(),
+                    dev: iface.name.clone(),
+                    forward: self.topology.is_forward(iface),
+                    delay_ms: conditions.delay_ms,
+                    netem: conditions.netem,
+                }
+            })
+            .collect();
+        Some(outage::schedule(self.outages.clone(), targets))
+    }
+
+    pub fn path_changes(&self) -> &[PathChange] {
+        &self.path_changes
+    }
+
+    /// Start the path change schedule, if any. The returned schedule must be
+    /// stopped once the client run completes.
+    pub fn schedule_path_changes(&self) -> Option<Worker<()>> {
+        if cfg!(not(target_os = "linux")) || self.path_changes.is_empty() {
+            // No network emulation on macOS
+            return None;
+        }
+
+        let targets = PathChangeTargets {
+            nat: self.nat.as_ref().map(|nat| nat.node.clone()),
+            clients: self.migrating_clients(),
+        };
+        Some(nat::schedule_path_changes(
+            self.path_changes.clone(),
+            targets,
+        ))
+    }
+
+    /// Every client, with a second address on its first interface, when a
+    /// path change migrates them.
+    fn migrating_clients(&self) -> Vec<MigratingClient> {
+        let migrates = self
+            .path_changes
+            .iter()
+            .any(|change| change.kind == PathChangeKind::Migrate);
+        if !migrates {
+            return Vec::new();
+        }
+
+        self.topology
+            .clients()
+            .filter_map(|node| {
+                let iface = self.topology.node_ifaces(&node.name).next()?;
+                let addrs = [Some(iface.addr), iface.addr6]
+                    .into_iter()
+                    .flatten()
+                    .map(|addr| (addr, addr.last_host()))
+                    .collect();
+                Some(MigratingClient {
+                    ns: node.name.clone(),
+                    addrs,
+                    routes: self.topology.routes(&node.name),
+                })
+            })
+            .collect()
+    }
+
+    /// `tc -s qdisc show` of the leaf qdisc of every htb shaper, taken before
+    /// and after a client run to count its ECN marks and drops. Policers
+    /// have no leaf qdisc and never mark.
+    pub fn qdisc_stats(&self) -> Result<String> {
+        if cfg!(not(target_os = "linux")) {
+            // No network emulation on macOS
+            return Ok(String::new());
+        }
+
+        let mut stats = String::new();
+        for iface in self
+            .qdisc_ifaces(Qdisc::shapes)
+            .filter(|iface| !self.is_policed(iface))
+        {
+            stats.push_str(&tc::htb_leaf_stats(&iface.node, &iface.name)?);
+        }
+        Ok(stats)
+    }
+
+    /// Capture the first interface of every namespace in `namespaces` to
+    /// `<dir>/run_<run>_<namespace>.pcap` until the returned capture is
+    /// dropped.
+    pub fn capture(&self, dir: &str, run: u16, namespaces: &[&str]) -> Result<Capture> {
+        if cfg!(not(target_os = "linux")) {
+            // No network emulation on macOS
+            return Capture::start(&[]);
+        }
+
+        let ifaces: Vec<_> = namespaces
+            .iter()
+            .filter_map(|ns| self.topology.node_ifaces(ns).next())
+            .map(|iface| {
+                let file = format!("{dir}/run_{run}_{}.pcap", iface.node);
+                (iface.node.clone(), iface.name.clone(), file)
+            })
+            .collect();
+        Capture::start(&ifaces)
+    }
+
+    /// Start sampling the qdisc or policer of every hop, and the bytes
+    /// received by every client, for the duration of a client run.
+    pub fn monitor_qdiscs(&self) -> Result<Worker<QdiscRun>> {
+        if cfg!(not(target_os = "linux")) {
+            // No network emulation on macOS
+            return monitor::start(Vec::new(), Vec::new());
+        }
+
+        let hops = self
+            .topology
+            .ifaces()
+            .filter(|iface| iface.qdisc.is_some())
+            .map(|iface| {
+                let counters = if self.is_policed(iface) {
+                    HopCounters::Police
+                } else if iface.qdisc.is_some_and(Qdisc::shapes) {
+                    HopCounters::Htb
+                } else {
+                    HopCounters::Root
+                };
+                (iface.node.clone(), iface.name.clone(), counters)
+            })
+            .collect();
+        let clients = self
+            .topology
+            .clients()
+            .filter_map(|client| self.topology.node_ifaces(&client.name).next())
+            .map(|iface| (iface.node.clone(), iface.name.clone()))
+            .collect();
+        monitor::start(hops, clients)
+    }
+
+    /// Every interface whose qdisc `has` something, e.g. `Qdisc::shapes`.
+    fn qdisc_ifaces(&self, has: fn(Qdisc) -> bool) -> impl Iterator<Item = &Iface> {
+        self.topology
+            .ifaces()
+            .filter(move |iface| iface.qdisc.is_some_and(has))
+    }
+
+    /// Whether `iface` polices its rate instead of shaping it.
+    fn is_policed(&self, iface: &Iface) -> bool {
+        iface.qdisc.is_some_and(Qdisc::shapes) && self.conditions(iface).police_burst.is_some()
+    }
+
+    /// The qdiscs of `iface` from the root down, with the conditions of its
+    /// direction and router.
+    fn qdisc_layout(&self, iface: &Iface) -> Vec<QdiscLayer> {
+        let Some(qdisc) = iface.qdisc else {
+            return Vec::new();
+        };
+        let conditions = self.conditions(iface);
+
+        let mut layout = Vec::new();
+        if qdisc == Qdisc::Fq {
+            layout.push(QdiscLayer::Fq);
+        }
+        if qdisc.delays() {
+            layout.push(QdiscLayer::Netem {
+                delay_ms: conditions.delay_ms,
+                netem: conditions.netem.clone(),
+            });
+        }
+        if qdisc.shapes() {
+            layout.push(match conditions.police_burst {
+                Some(burst) => QdiscLayer::Police {
+                    rate_mbit: conditions.rate_mbit,
+                    // a burst below a packet would drop every packet
+                    burst_bytes: burst
+                        .bytes(conditions.bdp_bytes(self.base_rtt_ms))
+                        .max(PACKET_BYTES),
+                },
+                None => QdiscLayer::Htb {
+                    rate_mbit: conditions.rate_mbit,
+                    queue: conditions.queue,
+                },
+            });
+        }
+
+        layout
+    }
+
+    /// Conditions of the direction packets leaving `iface` travel in, with
+    /// the overrides of its router.
+    fn conditions(&self, iface: &Iface) -> Conditions {
+        if !self.topology.is_forward(iface) {
+            return self.reverse.clone();
+        }
+        match self.hops.iter().find(|hop| hop.node == iface.node) {
+            Some(hop) => hop.apply(&self.forward),
+            None => self.forward.clone(),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn create_qdiscs(&self) -> Result<()> {
+        for iface in self.topology.ifaces() {
+            let (ns, dev) = (iface.node.as_str(), iface.name.as_str());
+            let layout = self.qdisc_layout(iface);
+            let below_netem = matches!(layout.first(), Some(QdiscLayer::Netem { .. }));
+            for layer in &layout {
+                match layer {
+                    QdiscLayer::Fq => tc::fq(ns, dev)?,
+                    QdiscLayer::Netem { delay_ms, netem } => tc::netem(ns, dev, *delay_ms, netem)?,
+                    QdiscLayer::Htb { rate_mbit, queue } => {
+                        tc::htb(ns, dev, *rate_mbit, below_netem)?;
+                        self.create_queue(ns, dev, &self.conditions(iface), queue)?;
+                    }
+                    QdiscLayer::Police {
+                        rate_mbit,
+                        burst_bytes,
+                    } => tc::police(ns, dev, *rate_mbit, *burst_bytes)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attach `queue` to an htb shaper, or its fallback if the kernel does
+    /// not support it.
+    #[cfg(target_os = "linux")]
+    fn create_queue(
+        &self,
+        ns: &str,
+        dev: &str,
+        conditions: &Conditions,
+        queue: &Queue,
+    ) -> Result<()> {
+        let bdp_bytes = conditions.bdp_bytes(self.base_rtt_ms);
+        let args = queue.args(conditions.buffer, bdp_bytes, conditions.rate_mbit);
+        match (tc::htb_leaf(ns, dev, &args), queue.fallback()) {
+            (Err(err), Some(fallback)) => {
+                println!(
+                    "{} unavailable on {ns}/{dev}, using {}: {err:?}",
+                    queue.kind, fallback.kind
+                );
+                self.create_queue(ns, dev, conditions, &fallback)
+            }
+            (res, _) => res,
+        }
+    }
+}
+
+/// One qdisc of an interface, see `NetworkSetup::qdisc_layout`.
+#[derive(Debug, Clone, PartialEq)]
+enum QdiscLayer {
+    Fq,
+    /// At the root, above the shaper of the interface if it has one.
+    Netem {
+        delay_ms: u64,
+        netem: Netem,
+    },
+    /// An htb shaper holding its queue in the leaf qdisc `queue`.
+    Htb {
+        rate_mbit: u64,
+        queue: Queue,
+    },
+    /// A policer in place of the htb shaper, next to the root qdisc.
+    Police {
+        rate_mbit: u64,
+        burst_bytes: u64,
+    },
+}
+
+/// Tears down the network created by `NetworkSetup::create` when dropped, on
+/// both return and unwind, and restores the host-wide sysctls.
+#[must_use = "the network is torn down when the guard is dropped"]
+pub struct NetworkGuard<'a> {
+    network: &'a NetworkSetup,
+    /// Values of the host-wide sysctls before the network was built.
+    #[cfg(target_os = "linux")]
+    host_sysctls: sysctl::HostSysctls,
+}
+
+impl Drop for NetworkGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.network.cleanup() {
+            eprintln!("Failed to tear down the network: {err:?}");
+        }
+        #[cfg(target_os = "linux")]
+        if let Err(err) = self.host_sysctls.restore() {
+            eprintln!("Failed to restore the host sysctls: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn conditions(delay_ms: u64, rate_mbit: u64) -> Conditions {
+        Conditions {
+            delay_ms,
+            netem: Netem::default(),
+            rate_mbit,
+            queue: "pfifo".parse().unwrap(),
+            buffer: BufferSize::Packets(100),
+            police_burst: None,
+        }
+    }
+
+    #[test]
+    fn parking_lot_hops() {
+        let topology = Topology::from_file(Path::new("topologies/parking_lot.toml")).unwrap();
+        let network = NetworkSetup::new(topology, conditions(10, 20), conditions(10, 20));
+        // netem on the three server to client hops, and on the client to
+        // server hops of m2 and m3
+        assert_eq!(network.base_rtt_ms, 50);
+        assert_eq!(network.forward_rate_mbit(), 20);
+
+        let network = network.with_hops(vec![
+            "ns_m2:rate_mbit=5".parse().unwrap(),
+            "ns_m3:rate_mbit=8,queue=fq_codel".parse().unwrap(),
+        ]);
+        assert_eq!(network.forward_rate_mbit(), 5);
+        let m3 = network
+            .topology
+            .node_ifaces("ns_m3")
+            .find(|iface| iface.name == "veth_m3_c1")
+            .unwrap();
+        assert_eq!(network.conditions(m3).queue, "fq_codel".parse().unwrap());
+        // the reverse direction keeps its conditions
+        let m3 = network
+            .topology
+            .node_ifaces("ns_m3")
+            .find(|iface| iface.name == "veth_m3_m2")
+            .unwrap();
+        assert_eq!(network.conditions(m3).rate_mbit, 20);
+    }
+
+    #[test]
+    fn hop_with_rate_and_delay() {
+        let topology = Topology::from_file(Path::new("topologies/parking_lot.toml")).unwrap();
+        let iface = |name| topology.ifaces().find(|iface| iface.name == name).unwrap();
+        let network = NetworkSetup::new(topology.clone(), conditions(10, 20), conditions(10, 20))
+            .with_hops(vec![
+                "ns_m2:rate_mbit=5,delay_ms=30,loss=1".parse().unwrap(),
+            ]);
+
+        // delayed and dropped by netem before queueing for the rate
+        assert_eq!(
+            network.qdisc_layout(iface("veth_m2_m3")),
+            [
+                QdiscLayer::Netem {
+                    delay_ms: 30,
+                    netem: Netem {
+                        loss: "1".parse().unwrap(),
+                        ..Netem::default()
+                    },
+                },
+                QdiscLayer::Htb {
+                    rate_mbit: 5,
+                    queue: "pfifo".parse().unwrap(),
+                },
+            ]
+        );
+        assert_eq!(
+            network.qdisc_layout(iface("veth_m1_m2"))[0],
+            QdiscLayer::Netem {
+                delay_ms: 10,
+                netem: Netem::default(),
+            }
+        );
+        assert_eq!(network.base_rtt_ms, 70);
+
+        let network = network.with_hops(vec!["ns_m3:police=10p".parse().unwrap()]);
+        assert_eq!(
+            network.qdisc_layout(iface("veth_m3_c1"))[1],
+            QdiscLayer::Police {
+                rate_mbit: 20,
+                burst_bytes: 10 * PACKET_BYTES,
+            }
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    error::Result,
+    network::{tc, worker::Worker},
+    stats::qdisc::{QdiscCounters, QdiscRun},
+};
+use std::time::{Duration, Instant};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::{
+            error::{BoarError, TopologyError},
+            network::netlink::{self, Netlink},
+        };
+    }
+}
+
+/// The backlog is a gauge, so it is sampled to find its maximum.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The counters a hop is sampled from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HopCounters {
+    /// Its root qdisc.
+    Root,
+    /// Its htb shaper, which may sit below a netem qdisc.
+    Htb,
+    /// Its policer.
+    Police,
+}
+
+/// Snapshot the counters of `hops`, (namespace, dev, counters), and keep
+/// sampling their backlog until stopped. The bytes received by the interface
+/// of every one of `clients`, (namespace, dev), are sampled too. Stopping the
+/// monitor returns the per-hop deltas of the run.
+///
+/// Only the first and the last snapshot must succeed: a sample which fails
+/// in between is skipped, and reported once the run is over.
+pub(crate) fn start(
+    hops: Vec<(String, String, HopCounters)>,
+    clients: Vec<(String, String)>,
+) -> Result<Worker<QdiscRun>> {
+    let first = snapshot(&hops)?;
+    let start = Instant::now();
+    let clients = clients
+        .iter()
+        .map(|(ns, dev)| {
+            let counter = RxCounter::open(ns, dev)?;
+            let first_rx = counter.read()?;
+            Ok((ns.clone(), counter, first_rx))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Worker::spawn("qdisc monitor", move |stop| {
+        let mut max_backlog: Vec<_> = first.iter().map(|c| c.backlog_bytes).collect();
+        let mut delivered: Vec<_> = clients
+            .iter()
+            .map(|(ns, _, _)| (ns.clone(), Vec::new()))
+            .collect();
+        let mut sample = || -> Result<()> {
+            // one time for all clients, so that their samples line up
+            let at = start.elapsed();
+            let mut result = Ok(());
+            for ((_, counter, first_rx), (_, samples)) in clients.iter().zip(&mut delivered) {
+                match counter.read() {
+                    Ok(rx) => samples.push((at, rx.saturating_sub(*first_rx))),
+                    Err(err) => result = result.and(Err(err)),
+                }
+            }
+            result
+        };
+        let mut skipped = 0;
+        let mut first_err = None;
+        let mut skip = |err| {
+            skipped += 1;
+            first_err.get_or_insert(err);
+        };
+        sample()?;
+        while stop.sleep(SAMPLE_INTERVAL) {
+            if let Err(err) = sample() {
+                skip(err);
+            }
+            match snapshot(&hops) {
+                Ok(counters) => {
+                    for (max, counters) in max_backlog.iter_mut().zip(counters) {
+                        *max = (*max).max(counters.backlog_bytes);
+                    }
+                }
+                Err(err) => skip(err),
+            }
+        }
+        sample()?;
+        let last = snapshot(&hops)?;
+        if let Some(err) = first_err {
+            eprintln!("Skipped {skipped} qdisc samples, the first failing with: {err:?}");
+        }
+
+        let hops: Vec<_> = hops
+            .iter()
+            .zip(first)
+            .zip(last)
+            .zip(max_backlog)
+            .map(|((((ns, dev, _), first), last), max_backlog)| {
+                let max_backlog = max_backlog.max(last.backlog_bytes);
+                (format!("{ns}/{dev}"), first, last, max_backlog)
+            })
+            .collect();
+        Ok(QdiscRun::new(&hops, delivered))
+    }))
+}
+
+fn snapshot(hops: &[(String, String, HopCounters)]) -> Result<Vec<QdiscCounters>> {
+    hops.iter()
+        .map(|(ns, dev, counters)| {
+            let stats = match counters {
+                HopCounters::Root => tc::root_stats(ns, dev)?,
+                HopCounters::Htb => tc::htb_stats(ns, dev)?,
+                HopCounters::Police => tc::police_stats(ns, dev)?,
+            };
+            Ok(QdiscCounters::new_from_qdisc_stats(&stats))
+        })
+        .collect()
+}
+
+/// Reads the bytes received by an interface over one netlink connection,
+/// opened in its namespace for the whole run.
+struct RxCounter {
+    #[cfg(target_os = "linux")]
+    ns: String,
+    #[cfg(target_os = "linux")]
+    dev: String,
+    #[cfg(target_os = "linux")]
+    nl: Netlink,
+    #[cfg(target_os = "linux")]
+    index: u32,
+}
+
+impl RxCounter {
+    fn open(ns: &str, dev: &str) -> Result<Self> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                // the socket stays in the namespace it was opened in
+                let nl = netlink::in_netns(ns, || {
+                    Netlink::new().map_err(|source| {
+                        TopologyError::Connect {
+                            ns: ns.to_string(),
+                            source,
+                        }
+                        .into()
+                    })
+                })?;
+                let counter = RxCounter {
+                    ns: ns.to_string(),
+                    dev: dev.to_string(),
+                    index: 0,
+                    nl,
+                };
+                let index = counter.nl.link_index(dev).ok_or_else(|| counter.not_found())?;
+                Ok(RxCounter { index, ..counter })
+            } else {
+                // No network emulation on macOS
+                let _ = (ns, dev);
+                Ok(RxCounter {})
+            }
+        }
+    }
+
+    /// Bytes received by the interface since it was created.
+    fn read(&self) -> Result<u64> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                self.nl.rx_bytes(self.index).ok_or_else(|| self.not_found())
+            } else {
+                // No network emulation on macOS
+                Ok(0)
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn not_found(&self) -> BoarError {
+        TopologyError::LinkNotFound {
+            ns: self.ns.clone(),
+            link: self.dev.clone(),
+        }
+        .into()
+    }
+}
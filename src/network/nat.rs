@@ -0,0 +1,312 @@
+use crate::{
+    error::Result,
+    network::{
+        topology::{IfaceAddr, Route},
+        worker::Worker,
+    },
+};
+use std::{str::FromStr, time::Duration};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::{
+            error::TopologyError,
+            network::{
+                netlink::{self, Netlink},
+                nft,
+                sysctl::{self, Sysctl},
+                topology::Topology,
+            },
+        };
+        use std::process::Command;
+    }
+}
+
+/// Masquerade of everything a middle node forwards towards the servers, as a
+/// home router or carrier-grade NAT would.
+#[derive(Debug, Clone)]
+pub struct Nat {
+    pub node: String,
+    /// Idle time after which conntrack forgets a UDP mapping.
+    pub mapping_timeout: Duration,
+}
+
+/// A change of the client's path to the server, relative to the start of
+/// each client run.
+///
+/// `AT_MS:rebind|migrate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathChange {
+    pub at: Duration,
+    pub kind: PathChangeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathChangeKind {
+    /// Forget the NAT mappings, so that the next packet of a client is sent
+    /// from a new port.
+    Rebind,
+    /// Switch the clients between their address and a second one on the same
+    /// interface.
+    Migrate,
+}
+
+impl FromStr for PathChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (at, kind) = s
+            .split_once(':')
+            .ok_or_else(|| format!("{s}: expected AT_MS:rebind|migrate"))?;
+        let at = at.parse().map_err(|err| format!("{s}: {err}"))?;
+        let kind = match kind {
+            "rebind" => PathChangeKind::Rebind,
+            "migrate" => PathChangeKind::Migrate,
+            _ => return Err(format!("{s}: expected rebind or migrate")),
+        };
+
+        Ok(PathChange {
+            at: Duration::from_millis(at),
+            kind,
+        })
+    }
+}
+
+/// A client which moves between two addresses of every family on
+/// `Migrate`.
+#[derive(Debug, Clone)]
+pub(crate) struct MigratingClient {
+    pub ns: String,
+    /// (address, migration address)
+    pub addrs: Vec<(IfaceAddr, IfaceAddr)>,
+    /// Routes whose preferred source is switched.
+    pub routes: Vec<Route>,
+}
+
+/// The namespaces `schedule_path_changes` acts on.
+#[derive(Debug, Clone)]
+pub(crate) struct PathChangeTargets {
+    pub nat: Option<String>,
+    pub clients: Vec<MigratingClient>,
+}
+
+/// Start the path changes of a client run. Stopping the schedule moves
+/// migrated clients back to their address.
+pub(crate) fn schedule_path_changes(
+    mut changes: Vec<PathChange>,
+    targets: PathChangeTargets,
+) -> Worker<()> {
+    Worker::spawn("path change schedule", move |stop| {
+        changes.sort_by_key(|change| change.at);
+
+        let mut migrated = false;
+        let mut res = Ok(());
+        let mut stopped_early = false;
+        for change in changes {
+            if !stop.sleep_until(change.at) {
+                stopped_early = true;
+                break;
+            }
+
+            res = match change.kind {
+                PathChangeKind::Rebind => rebind(&targets),
+                PathChangeKind::Migrate => {
+                    migrated = !migrated;
+                    migrate(&targets, migrated)
+                }
+            };
+            if res.is_err() {
+                break;
+            }
+        }
+        if !stopped_early {
+            // hold the last change until the run ends
+            stop.wait();
+        }
+
+        // start the next run from the original address
+        if migrated {
+            res = res.and(migrate(&targets, false));
+        }
+        res
+    })
+}
+
+/// Delete the UDP mappings of the NAT.
+fn rebind(targets: &PathChangeTargets) -> Result<()> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            let Some(ns) = &targets.nat else {
+                return Ok(());
+            };
+            let mut cmd = Command::new("conntrack");
+            cmd.args(["-D", "-p", "udp"]);
+            let res = netlink::exec_in_netns(&mut cmd, ns).and_then(|()| cmd.output());
+
+            let stderr = match res {
+                Ok(res) if res.status.success() => return Ok(()),
+                Ok(res) => String::from_utf8_lossy(&res.stderr).trim().to_string(),
+                Err(err) => err.to_string(),
+            };
+            // conntrack fails when there was no mapping to delete
+            if stderr.contains("0 flow entries") {
+                return Ok(());
+            }
+            Err(TopologyError::Nat {
+                ns: ns.clone(),
+                stderr,
+            }
+            .into())
+        } else {
+            // No network emulation on macOS
+            let _ = targets;
+            Ok(())
+        }
+    }
+}
+
+/// Prefer the migration address of every client, or its original address.
+fn migrate(targets: &PathChangeTargets, to_migration_addr: bool) -> Result<()> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            for client in &targets.clients {
+                netlink::in_netns(&client.ns, || {
+                    let nl = Netlink::new().map_err(|source| TopologyError::Connect {
+                        ns: client.ns.clone(),
+                        source,
+                    })?;
+                    for route in &client.routes {
+                        let family = |addr: &IfaceAddr| addr.ip.is_ipv6() == route.via.is_ipv6();
+                        let Some((addr, migration_addr)) =
+                            client.addrs.iter().find(|(addr, _)| family(addr))
+                        else {
+                            continue;
+                        };
+                        let src = if to_migration_addr {
+                            migration_addr.ip
+                        } else {
+                            addr.ip
+                        };
+                        nl.replace_route(*route, src)
+                            .map_err(|source| TopologyError::AddRoute {
+                                ns: client.ns.clone(),
+                                route: *route,
+                                source,
+                            })?;
+                    }
+                    Ok(())
+                })?;
+            }
+
+            Ok(())
+        } else {
+            // No network emulation on macOS
+            let _ = (targets, to_migration_addr);
+            Ok(())
+        }
+    }
+}
+
+/// Masquerade the traffic `nat.node` forwards towards the servers and apply
+/// the mapping timeout.
+#[cfg(target_os = "linux")]
+pub(crate) fn create(topology: &Topology, nat: &Nat) -> Result<()> {
+    let mut wan = Vec::new();
+    for server in topology.servers() {
+        let iface = topology
+            .egress_path(&nat.node, &server.name)
+            .first()
+            .map(|iface| format!("\"{}\"", iface.name))
+            .expect("validated NAT node");
+        if !wan.contains(&iface) {
+            wan.push(iface);
+        }
+    }
+    let wan = wan.join(", ");
+
+    // random ports, so that a rebind gets a new mapping
+    let ruleset = format!(
+        "table inet boar_nat {{\n\
+         \tchain postrouting {{\n\
+         \t\ttype nat hook postrouting priority srcnat; policy accept;\n\
+         \t\toifname {{ {wan} }} masquerade random\n\
+         \t}}\n\
+         }}\n"
+    );
+    nft::load(&nat.node, &ruleset).map_err(|stderr| TopologyError::Nat {
+        ns: nat.node.clone(),
+        stderr,
+    })?;
+
+    // the conntrack sysctls only exist once nft loaded the nat module
+    let timeout = nat.mapping_timeout.as_secs().to_string();
+    let sysctls: Vec<_> = [
+        "net.netfilter.nf_conntrack_udp_timeout",
+        "net.netfilter.nf_conntrack_udp_timeout_stream",
+    ]
+    .into_iter()
+    .map(|key| Sysctl {
+        key: key.to_string(),
+        value: timeout.clone(),
+    })
+    .collect();
+    netlink::in_netns(&nat.node, || sysctl::write(&nat.node, &sysctls))
+}
+
+/// Add the migration address of every client to its interface.
+#[cfg(target_os = "linux")]
+pub(crate) fn add_migration_addrs(topology: &Topology, clients: &[MigratingClient]) -> Result<()> {
+    for client in clients {
+        let iface = topology
+            .node_ifaces(&client.ns)
+            .next()
+            .expect("validated topology");
+        netlink::in_netns(&client.ns, || {
+            let nl = Netlink::new().map_err(|source| TopologyError::Connect {
+                ns: client.ns.clone(),
+                source,
+            })?;
+            let index = nl
+                .link_index(&iface.name)
+                .ok_or_else(|| TopologyError::LinkNotFound {
+                    ns: client.ns.clone(),
+                    link: iface.name.clone(),
+                })?;
+            for (_, migration_addr) in &client.addrs {
+                nl.add_addr(index, *migration_addr).map_err(|source| {
+                    TopologyError::AddAddress {
+                        ns: client.ns.clone(),
+                        link: iface.name.clone(),
+                        addr: *migration_addr,
+                        source,
+                    }
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_path_change() {
+        assert_eq!(
+            "1500:rebind".parse::<PathChange>(),
+            Ok(PathChange {
+                at: Duration::from_millis(1500),
+                kind: PathChangeKind::Rebind,
+            })
+        );
+        assert_eq!(
+            "0:migrate".parse::<PathChange>().map(|change| change.kind),
+            Ok(PathChangeKind::Migrate)
+        );
+        assert!("1500".parse::<PathChange>().is_err());
+        assert!("1500:teleport".parse::<PathChange>().is_err());
+    }
+}
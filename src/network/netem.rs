@@ -0,0 +1,396 @@
+use std::{fmt, str::FromStr};
+
+/// Impairments applied by netem on top of its delay.
+///
+/// Every impairment can be given on the command line in a compact
+/// colon-separated form, see the `FromStr` implementations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Netem {
+    pub loss: LossModel,
+    pub jitter: Option<Jitter>,
+    pub reorder: Option<Reorder>,
+    pub duplicate: Option<Probability>,
+    pub corrupt: Option<Probability>,
+}
+
+/// netem loss models.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LossModel {
+    /// Independent loss, optionally correlated with the previous packet.
+    ///
+    /// `random:PCT[:CORR]`, or just `PCT`.
+    Random(Probability),
+    /// Gilbert-Elliott two state model. `p` and `r` are the transition
+    /// probabilities into and out of the bad state, `one_minus_h` and
+    /// `one_minus_k` the loss probabilities in the bad and good state.
+    ///
+    /// `gemodel:P[:R[:1-H[:1-K]]]`
+    GilbertElliott {
+        p: f64,
+        r: Option<f64>,
+        one_minus_h: Option<f64>,
+        one_minus_k: Option<f64>,
+    },
+    /// 4-state Markov model with transition probabilities between the good
+    /// (1), good-loss (4), bad-reception (3) and burst-loss (2) states.
+    ///
+    /// `state:P13[:P31[:P32[:P23[:P14]]]]`
+    FourState {
+        p13: f64,
+        p31: Option<f64>,
+        p32: Option<f64>,
+        p23: Option<f64>,
+        p14: Option<f64>,
+    },
+}
+
+/// A percentage with an optional correlation (%) to the previous packet.
+///
+/// `PCT[:CORR]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Probability {
+    pub pct: f64,
+    pub correlation_pct: Option<f64>,
+}
+
+/// Delay variation around the netem delay.
+///
+/// `MS[:CORR][:DISTRIBUTION]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Jitter {
+    pub ms: u64,
+    pub correlation_pct: Option<f64>,
+    pub distribution: Option<Distribution>,
+}
+
+/// Distribution tables shipped with iproute2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    Uniform,
+    Normal,
+    Pareto,
+    ParetoNormal,
+}
+
+/// Send a percentage of packets immediately, reordering them ahead of the
+/// delayed ones. With a gap, every `gap`-th packet is reordered instead.
+///
+/// `PCT[:CORR[:GAP]]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reorder {
+    pub probability: Probability,
+    pub gap: Option<u32>,
+}
+
+impl Netem {
+    /// netem arguments following `delay`.
+    pub fn args(&self, delay_ms: u64) -> Vec<String> {
+        let mut args = vec!["delay".to_string(), format!("{delay_ms}ms")];
+        if let Some(jitter) = &self.jitter {
+            args.push(format!("{}ms", jitter.ms));
+            push_pct(&mut args, jitter.correlation_pct);
+            if let Some(distribution) = jitter.distribution {
+                args.extend(["distribution".to_string(), distribution.to_string()]);
+            }
+        }
+
+        args.push("loss".to_string());
+        match &self.loss {
+            LossModel::Random(probability) => {
+                args.push("random".to_string());
+                probability.push_args(&mut args);
+            }
+            LossModel::GilbertElliott {
+                p,
+                r,
+                one_minus_h,
+                one_minus_k,
+            } => {
+                args.push("gemodel".to_string());
+                push_pct(&mut args, Some(*p));
+                for pct in [r, one_minus_h, one_minus_k]
+                    .into_iter()
+                    .map_while(|pct| *pct)
+                {
+                    push_pct(&mut args, Some(pct));
+                }
+            }
+            LossModel::FourState {
+                p13,
+                p31,
+                p32,
+                p23,
+                p14,
+            } => {
+                args.push("state".to_string());
+                push_pct(&mut args, Some(*p13));
+                for pct in [p31, p32, p23, p14].into_iter().map_while(|pct| *pct) {
+                    push_pct(&mut args, Some(pct));
+                }
+            }
+        }
+
+        if let Some(reorder) = &self.reorder {
+            args.push("reorder".to_string());
+            reorder.probability.push_args(&mut args);
+            if let Some(gap) = reorder.gap {
+                args.extend(["gap".to_string(), gap.to_string()]);
+            }
+        }
+        if let Some(duplicate) = &self.duplicate {
+            args.push("duplicate".to_string());
+            duplicate.push_args(&mut args);
+        }
+        if let Some(corrupt) = &self.corrupt {
+            args.push("corrupt".to_string());
+            corrupt.push_args(&mut args);
+        }
+
+        args
+    }
+}
+
+impl Default for LossModel {
+    fn default() -> Self {
+        LossModel::Random(Probability {
+            pct: 0.0,
+            correlation_pct: None,
+        })
+    }
+}
+
+impl Probability {
+    fn push_args(&self, args: &mut Vec<String>) {
+        push_pct(args, Some(self.pct));
+        push_pct(args, self.correlation_pct);
+    }
+}
+
+fn push_pct(args: &mut Vec<String>, pct: Option<f64>) {
+    if let Some(pct) = pct {
+        args.push(format!("{pct}%"));
+    }
+}
+
+fn parse_pct(s: &str) -> Result<f64, String> {
+    let pct: f64 = s
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|err| format!("{s}: {err}"))?;
+    if !(0.0..=100.0).contains(&pct) {
+        return Err(format!("{s}: expected a percentage between 0 and 100"));
+    }
+    Ok(pct)
+}
+
+/// Parse up to `N` colon-separated percentages, of which `required` must be
+/// present.
+fn parse_pcts<const N: usize>(s: &str, required: usize) -> Result<[Option<f64>; N], String> {
+    let fields: Vec<_> = s.split(':').collect();
+    if fields.len() < required || fields.len() > N {
+        return Err(format!("{s}: expected {required} to {N} values"));
+    }
+
+    let mut pcts = [None; N];
+    for (pct, field) in pcts.iter_mut().zip(fields) {
+        *pct = Some(parse_pct(field)?);
+    }
+    Ok(pcts)
+}
+
+impl FromStr for LossModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (model, params) = s.split_once(':').unwrap_or(("random", s));
+        match model {
+            "random" => Ok(LossModel::Random(params.parse()?)),
+            "gemodel" => {
+                let [p, r, one_minus_h, one_minus_k] = parse_pcts(params, 1)?;
+                Ok(LossModel::GilbertElliott {
+                    p: p.expect("required"),
+                    r,
+                    one_minus_h,
+                    one_minus_k,
+                })
+            }
+            "state" => {
+                let [p13, p31, p32, p23, p14] = parse_pcts(params, 1)?;
+                Ok(LossModel::FourState {
+                    p13: p13.expect("required"),
+                    p31,
+                    p32,
+                    p23,
+                    p14,
+                })
+            }
+            model => Err(format!(
+                "unknown loss model {model}, expected random, gemodel or state"
+            )),
+        }
+    }
+}
+
+impl FromStr for Probability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [pct, correlation_pct] = parse_pcts(s, 1)?;
+        Ok(Probability {
+            pct: pct.expect("required"),
+            correlation_pct,
+        })
+    }
+}
+
+impl FromStr for Jitter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split(':');
+        let ms = fields
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches("ms")
+            .parse()
+            .map_err(|err| format!("{s}: {err}"))?;
+
+        let mut jitter = Jitter {
+            ms,
+            correlation_pct: None,
+            distribution: None,
+        };
+        for field in fields {
+            if let Ok(distribution) = field.parse() {
+                jitter.distribution = Some(distribution);
+            } else if jitter.correlation_pct.is_none() && jitter.distribution.is_none() {
+                jitter.correlation_pct = Some(parse_pct(field)?);
+            } else {
+                return Err(format!("{s}: expected MS[:CORR][:DISTRIBUTION]"));
+            }
+        }
+
+        Ok(jitter)
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Distribution::Uniform),
+            "normal" => Ok(Distribution::Normal),
+            "pareto" => Ok(Distribution::Pareto),
+            "paretonormal" => Ok(Distribution::ParetoNormal),
+            s => Err(format!("unknown distribution {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Distribution::Uniform => "uniform",
+            Distribution::Normal => "normal",
+            Distribution::Pareto => "pareto",
+            Distribution::ParetoNormal => "paretonormal",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Reorder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split(':').collect();
+        let (probability, gap) = match fields.as_slice() {
+            [pct] => (pct.parse()?, None),
+            [pct, corr] => (format!("{pct}:{corr}").parse()?, None),
+            [pct, corr, gap] => {
+                let gap = gap.parse().map_err(|err| format!("{s}: gap: {err}"))?;
+                (format!("{pct}:{corr}").parse()?, Some(gap))
+            }
+            _ => return Err(format!("{s}: expected PCT[:CORR[:GAP]]")),
+        };
+
+        Ok(Reorder { probability, gap })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_loss_models() {
+        assert_eq!(
+            "1.5".parse::<LossModel>().unwrap(),
+            LossModel::Random(Probability {
+                pct: 1.5,
+                correlation_pct: None,
+            })
+        );
+        assert_eq!(
+            "random:1:25".parse::<LossModel>().unwrap(),
+            LossModel::Random(Probability {
+                pct: 1.0,
+                correlation_pct: Some(25.0),
+            })
+        );
+        assert_eq!(
+            "gemodel:1:10".parse::<LossModel>().unwrap(),
+            LossModel::GilbertElliott {
+                p: 1.0,
+                r: Some(10.0),
+                one_minus_h: None,
+                one_minus_k: None,
+            }
+        );
+        assert!("state:1:2:3:4:5:6".parse::<LossModel>().is_err());
+        assert!("random:101".parse::<LossModel>().is_err());
+        assert!("bursty:1".parse::<LossModel>().is_err());
+    }
+
+    #[test]
+    fn parse_jitter() {
+        assert_eq!(
+            "10:25:normal".parse::<Jitter>().unwrap(),
+            Jitter {
+                ms: 10,
+                correlation_pct: Some(25.0),
+                distribution: Some(Distribution::Normal),
+            }
+        );
+        assert_eq!(
+            "10:pareto".parse::<Jitter>().unwrap(),
+            Jitter {
+                ms: 10,
+                correlation_pct: None,
+                distribution: Some(Distribution::Pareto),
+            }
+        );
+        assert!("10:pareto:25".parse::<Jitter>().is_err());
+    }
+
+    #[test]
+    fn netem_args() {
+        let netem = Netem {
+            loss: "gemodel:1:10:70:0.1".parse().unwrap(),
+            jitter: Some("5:25:normal".parse().unwrap()),
+            reorder: Some("25:50:5".parse().unwrap()),
+            duplicate: Some("1".parse().unwrap()),
+            corrupt: Some("0.1:10".parse().unwrap()),
+        };
+
+        assert_eq!(
+            netem.args(50).join(" "),
+            "delay 50ms 5ms 25% distribution normal \
+             loss gemodel 1% 10% 70% 0.1% \
+             reorder 25% 50% gap 5 \
+             duplicate 1% \
+             corrupt 0.1% 10%"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    error::{Result, TopologyError},
+    network::topology::{IfaceAddr, Route},
+};
+use futures::TryStreamExt;
+use netlink_packet_route::link::nlas::{Nla, Stats64Buffer};
+use nix::{
+    mount::{MntFlags, MsFlags, mount, umount2},
+    sched::{CloneFlags, setns, unshare},
+};
+use rtnetlink::{Handle, NetworkNamespace, new_connection};
+use std::{
+    fs::File,
+    io,
+    net::IpAddr,
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+};
+use tokio::runtime::{Builder, Runtime};
+
+pub(crate) const NETNS_DIR: &str = "/run/netns";
+
+/// Skip duplicate address detection, so that an IPv6 address can be used as
+/// soon as it is added. From linux/if_addr.h.
+const IFA_F_NODAD: u8 = 0x02;
+
+/// A blocking rtnetlink client bound to the namespace of the thread which
+/// created it.
+pub(crate) struct Netlink {
+    rt: Runtime,
+    handle: Handle,
+}
+
+impl Netlink {
+    pub fn new() -> io::Result<Self> {
+        let rt = Builder::new_current_thread().enable_io().build()?;
+        let handle = rt.block_on(async {
+            let (connection, handle, _) = new_connection()?;
+            tokio::spawn(connection);
+            io::Result::Ok(handle)
+        })?;
+
+        Ok(Netlink { rt, handle })
+    }
+
+    pub fn link_index(&self, name: &str) -> Option<u32> {
+        self.rt
+            .block_on(async {
+                let mut links = self
+                    .handle
+                    .link()
+                    .get()
+                    .match_name(name.to_string())
+                    .execute();
+                links.try_next().await.ok().flatten()
+            })
+            .map(|link| link.header.index)
+    }
+
+    /// Bytes received by the link `index` since it was created.
+    pub fn rx_bytes(&self, index: u32) -> Option<u64> {
+        let link = self.rt.block_on(async {
+            let mut links = self.handle.link().get().match_index(index).execute();
+            links.try_next().await.ok().flatten()
+        })?;
+        link.nlas.into_iter().find_map(|nla| match nla {
+            Nla::Stats64(stats) => Stats64Buffer::new_checked(&stats)
+                .ok()
+                .map(|stats| stats.rx_bytes()),
+            _ => None,
+        })
+    }
+
+    pub fn add_veth(&self, name: &str, peer: &str) -> io::Result<()> {
+        let req = self
+            .handle
+            .link()
+            .add()
+            .veth(name.to_string(), peer.to_string());
+        self.rt.block_on(req.execute()).map_err(to_io)
+    }
+
+    pub fn del_link(&self, index: u32) -> io::Result<()> {
+        let req = self.handle.link().del(index);
+        self.rt.block_on(req.execute()).map_err(to_io)
+    }
+
+    pub fn move_link(&self, index: u32, ns: &str) -> io::Result<()> {
+        let ns = File::open(netns_path(ns))?;
+        let req = self.handle.link().set(index).setns_by_fd(ns.as_raw_fd());
+        self.rt.block_on(req.execute()).map_err(to_io)
+    }
+
+    pub fn link_up(&self, index: u32) -> io::Result<()> {
+        let req = self.handle.link().set(index).up();
+        self.rt.block_on(req.execute()).map_err(to_io)
+    }
+
+    pub fn set_mtu(&self, index: u32, mtu: u32) -> io::Result<()> {
+        let req = self.handle.link().set(index).mtu(mtu);
+        self.rt.block_on(req.execute()).map_err(to_io)
+    }
+
+    pub fn add_addr(&self, index: u32, addr: IfaceAddr) -> io::Result<()> {
+        let mut req = self.handle.address().add(index, addr.ip, addr.prefix_len);
+        if addr.ip.is_ipv6() {
+            req.message_mut().header.flags |= IFA_F_NODAD;
+        }
+        self.rt.block_on(req.execute()).map_err(to_io)
+    }
+
+    pub fn add_route(&self, route: Route) -> io::Result<()> {
+        self.set_route(route, None)
+    }
+
+    /// Replace the route to `route.dst`, preferring `src` as the source
+    /// address of new packets.
+    pub fn replace_route(&self, route: Route, src: IpAddr) -> io::Result<()> {
+        self.set_route(route, Some(src))
+    }
+
+    fn set_route(&self, route: Route, src: Option<IpAddr>) -> io::Result<()> {
+        let req = self.handle.route().add();
+        let res = match (route.dst.ip, route.via, src) {
+            (IpAddr::V4(dst), IpAddr::V4(via), None | Some(IpAddr::V4(_))) => {
+                let mut req = req
+                    .v4()
+                    .destination_prefix(dst, route.dst.prefix_len)
+                    .gateway(via);
+                if let Some(IpAddr::V4(src)) = src {
+                    req = req.pref_source(src).replace();
+                }
+                self.rt.block_on(req.execute())
+            }
+            (IpAddr::V6(dst), IpAddr::V6(via), None | Some(IpAddr::V6(_))) => {
+                let mut req = req
+                    .v6()
+                    .destination_prefix(dst, route.dst.prefix_len)
+                    .gateway(via);
+                if let Some(IpAddr::V6(src)) = src {
+                    req = req.pref_source(src).replace();
+                }
+                self.rt.block_on(req.execute())
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "route address families differ",
+                ));
+            }
+        };
+
+        res.map_err(to_io)
+    }
+}
+
+fn to_io(err: rtnetlink::Error) -> io::Error {
+    match err {
+        rtnetlink::Error::NetlinkError(msg) => msg.to_io(),
+        err => io::Error::other(err),
+    }
+}
+
+pub(crate) fn netns_path(ns: &str) -> PathBuf {
+    Path::new(NETNS_DIR).join(ns)
+}
+
+pub(crate) fn netns_exists(ns: &str) -> bool {
+    netns_path(ns).exists()
+}
+
+/// Equivalent to `ip netns add`.
+pub(crate) fn add_netns(ns: &str) -> io::Result<()> {
+    let add = NetworkNamespace::add(ns.to_string());
+    Builder::new_current_thread()
+        .build()?
+        .block_on(add)
+        .map_err(to_io)
+}
+
+/// Equivalent to `ip netns del`.
+pub(crate) fn del_netns(ns: &str) -> io::Result<()> {
+    let del = NetworkNamespace::del(ns.to_string());
+    Builder::new_current_thread()
+        .build()?
+        .block_on(del)
+        .map_err(to_io)
+}
+
+/// Make `cmd` run in the namespace `ns`. Unlike `ip netns exec` this does
+/// not remount /sys, so it needs no privileges beyond those over `ns`.
+pub(crate) fn exec_in_netns(cmd: &mut Command, ns: &str) -> io::Result<()> {
+    let fd = File::open(netns_path(ns))?;
+    // SAFETY: setns is a single syscall, safe to make between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            setns(&fd, CloneFlags::CLONE_NEWNET)?;
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// `exec_in_netns`, and give `cmd` the sysfs of `ns` as `ip netns exec`
+/// does, for the settings only /sys/class/net exposes.
+pub(crate) fn exec_in_netns_with_sysfs(cmd: &mut Command, ns: &str) -> io::Result<()> {
+    exec_in_netns(cmd, ns)?;
+    // SAFETY: only syscalls, safe to make between fork and exec
+    unsafe {
+        cmd.pre_exec(|| {
+            unshare(CloneFlags::CLONE_NEWNS)?;
+            // keep the new sysfs from propagating to the parent
+            let none = None::<&str>;
+            mount(none, "/", none, MsFlags::MS_SLAVE | MsFlags::MS_REC, none)?;
+            // there may be no sysfs mounted yet
+            let _ = umount2("/sys", MntFlags::MNT_DETACH);
+            mount(Some("sysfs"), "/sys", Some("sysfs"), MsFlags::empty(), none)?;
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// Run `f` on a thread which has entered the namespace `ns`.
+///
+/// The namespace of a thread is sticky, so a scoped thread is used to leave
+/// the calling thread in its original namespace.
+pub(crate) fn in_netns<T, F>(ns: &str, f: F) -> Result<T>
+where
+    T: Send,
+    F: FnOnce() -> Result<T> + Send,
+{
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let enter = |ns: &str| -> io::Result<()> {
+                    let fd = File::open(netns_path(ns))?;
+                    setns(fd, CloneFlags::CLONE_NEWNET)?;
+                    Ok(())
+                };
+                enter(ns).map_err(|source| TopologyError::EnterNamespace {
+                    ns: ns.to_string(),
+                    source,
+                })?;
+
+                f()
+            })
+            .join()
+            .expect("namespace thread panicked")
+    })
+}
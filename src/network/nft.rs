@@ -0,0 +1,29 @@
+use crate::network::netlink;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Load `ruleset` with `nft -f` in the namespace `ns`, returning the stderr
+/// of nft if it fails.
+pub(crate) fn load(ns: &str, ruleset: &str) -> Result<(), String> {
+    let mut cmd = Command::new("nft");
+    cmd.args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let res = netlink::exec_in_netns(&mut cmd, ns)
+        .and_then(|()| cmd.spawn())
+        .and_then(|mut nft| {
+            let mut stdin = nft.stdin.take().expect("piped stdin");
+            stdin.write_all(ruleset.as_bytes())?;
+            drop(stdin);
+            nft.wait_with_output()
+        });
+
+    match res {
+        Ok(res) if res.status.success() => Ok(()),
+        Ok(res) => Err(String::from_utf8_lossy(&res.stderr).trim().to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::{
+            error::{Result, TopologyError},
+            network::{
+                netlink,
+                topology::{Iface, Topology},
+            },
+        };
+        use std::process::Command;
+    }
+}
+
+/// Batching and checksum offloads of every interface, and how their packets
+/// are polled. Batching lets a sender burst past the emulated rate, so it is
+/// off unless a run asks for it.
+///
+/// `KEY=on|off[,KEY=on|off...]` with the keys `rx`, `tx`, `tso`, `gso`, `gro`
+/// and `threaded_napi`, changing the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offload {
+    /// Checksum offload of received and sent packets.
+    pub rx: bool,
+    pub tx: bool,
+    /// TCP segmentation offload.
+    pub tso: bool,
+    /// Generic segmentation offload, which also carries UDP GSO batches.
+    pub gso: bool,
+    /// Generic receive offload, which coalesces packets before the qdiscs of
+    /// the next hop. veth only polls with NAPI while it is on.
+    pub gro: bool,
+    /// Poll every interface from its own kernel thread instead of softirq.
+    pub threaded_napi: bool,
+}
+
+impl Default for Offload {
+    fn default() -> Self {
+        Offload {
+            rx: true,
+            tx: true,
+            tso: false,
+            gso: false,
+            gro: false,
+            threaded_napi: false,
+        }
+    }
+}
+
+impl Offload {
+    /// ethtool features with the names `ethtool -K` takes and `ethtool -k`
+    /// prints.
+    fn features(&self) -> [(&'static str, &'static str, bool); 5] {
+        [
+            ("rx", "rx-checksumming", self.rx),
+            ("tx", "tx-checksumming", self.tx),
+            ("tso", "tcp-segmentation-offload", self.tso),
+            ("gso", "generic-segmentation-offload", self.gso),
+            ("gro", "generic-receive-offload", self.gro),
+        ]
+    }
+}
+
+impl FromStr for Offload {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut offload = Offload::default();
+        for setting in s.split(',') {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("{s}: expected KEY=on|off, got {setting:?}"))?;
+            let on = match value {
+                "on" => true,
+                "off" => false,
+                _ => return Err(format!("{s}: {key}: expected on or off")),
+            };
+            match key {
+                "rx" => offload.rx = on,
+                "tx" => offload.tx = on,
+                "tso" => offload.tso = on,
+                "gso" => offload.gso = on,
+                "gro" => offload.gro = on,
+                "threaded_napi" => offload.threaded_napi = on,
+                _ => {
+                    return Err(format!(
+                        "{s}: unknown key {key:?}, expected rx, tx, tso, gso, gro or threaded_napi"
+                    ));
+                }
+            }
+        }
+
+        Ok(offload)
+    }
+}
+
+/// Apply `offload` to every interface of `topology`, and check that the
+/// kernel did not keep a feature it cannot change.
+#[cfg(target_os = "linux")]
+pub(crate) fn set(topology: &Topology, offload: &Offload) -> Result<()> {
+    let on_off = |on| if on { "on" } else { "off" };
+
+    for iface in topology.ifaces() {
+        let mut args = vec!["-K", &iface.name];
+        for (key, _, on) in offload.features() {
+            args.extend([key, on_off(on)]);
+        }
+        let mut set = Command::new("ethtool");
+        set.args(args);
+        run(iface, set, false)?;
+
+        let mut get = Command::new("ethtool");
+        get.args(["-k", &iface.name]);
+        let features = run(iface, get, false)?;
+        for (_, feature, on) in offload.features() {
+            let prefix = format!("{feature}: {}", on_off(on));
+            if !features.lines().any(|line| line.starts_with(&prefix)) {
+                return Err(TopologyError::OffloadNotApplied {
+                    ns: iface.node.clone(),
+                    link: iface.name.clone(),
+                    feature: feature.to_string(),
+                }
+                .into());
+            }
+        }
+
+        if offload.threaded_napi {
+            let path = format!("/sys/class/net/{}/threaded", iface.name);
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", &format!("echo 1 > {path}")]);
+            run(iface, cmd, true)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `cmd` in the namespace of `iface`, with its sysfs if `sysfs`, and
+/// return its stdout.
+#[cfg(target_os = "linux")]
+fn run(iface: &Iface, mut cmd: Command, sysfs: bool) -> Result<String> {
+    let res = if sysfs {
+        netlink::exec_in_netns_with_sysfs(&mut cmd, &iface.node)
+    } else {
+        netlink::exec_in_netns(&mut cmd, &iface.node)
+    }
+    .and_then(|()| cmd.output());
+
+    let stderr = match res {
+        Ok(res) if res.status.success() => {
+            return Ok(String::from_utf8_lossy(&res.stdout).into_owned());
+        }
+        Ok(res) => String::from_utf8_lossy(&res.stderr).trim().to_string(),
+        Err(err) => err.to_string(),
+    };
+    Err(TopologyError::Offload {
+        ns: iface.node.clone(),
+        link: iface.name.clone(),
+        stderr,
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_offload() {
+        assert_eq!("gro=off".parse(), Ok(Offload::default()));
+
+        let offload: Offload = "gso=on,gro=on,threaded_napi=on".parse().unwrap();
+        assert!(offload.gso && offload.gro && offload.threaded_napi);
+        assert!(offload.rx && !offload.tso);
+
+        assert!("gro".parse::<Offload>().is_err());
+        assert!("gro=yes".parse::<Offload>().is_err());
+        assert!("lro=on".parse::<Offload>().is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    error::Result,
+    network::{
+        netem::{LossModel, Netem, Probability},
+        tc,
+        worker::Worker,
+    },
+};
+use std::{ops::Range, str::FromStr, time::Duration};
+
+/// A window of 100% loss, relative to the start of each client run.
+///
+/// `START_MS:DURATION_MS[:forward|reverse|both]`, where `both` drops
+/// everything and is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outage {
+    pub start: Duration,
+    pub duration: Duration,
+    pub direction: OutageDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutageDirection {
+    /// Server to client.
+    Forward,
+    /// Client to server.
+    Reverse,
+    Both,
+}
+
+impl Outage {
+    pub fn window(&self) -> Range<Duration> {
+        self.start..self.start + self.duration
+    }
+
+    /// Whether the outage drops what leaves a forward or reverse interface.
+    pub(crate) fn covers(&self, forward: bool) -> bool {
+        match self.direction {
+            OutageDirection::Forward => forward,
+            OutageDirection::Reverse => !forward,
+            OutageDirection::Both => true,
+        }
+    }
+}
+
+impl FromStr for Outage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split(':');
+        let mut ms = |name| {
+            fields
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|err| format!("{s}: {name}: {err}"))
+        };
+        let start = ms("start")?;
+        let duration = ms("duration")?;
+        let direction = match fields.next() {
+            None => OutageDirection::Both,
+            Some(direction) => direction.parse()?,
+        };
+        if fields.next().is_some() {
+            return Err(format!("{s}: expected START_MS:DURATION_MS[:DIRECTION]"));
+        }
+
+        Ok(Outage {
+            start,
+            duration,
+            direction,
+        })
+    }
+}
+
+impl FromStr for OutageDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forward" => Ok(OutageDirection::Forward),
+            "reverse" => Ok(OutageDirection::Reverse),
+            "both" => Ok(OutageDirection::Both),
+            _ => Err(format!(
+                "unknown direction {s:?}, expected forward, reverse or both"
+            )),
+        }
+    }
+}
+
+/// A netem qdisc an outage is applied to.
+#[derive(Debug, Clone)]
+pub(crate) struct OutageTarget {
+    pub ns: String,
+    pub dev: String,
+    /// Whether the interface carries server to client traffic.
+    pub forward: bool,
+    /// Delay and impairments restored when an outage ends.
+    pub delay_ms: u64,
+    pub netem: Netem,
+}
+
+/// Start the outages of a client run, applied by switching netem to 100%
+/// loss. Stopping the schedule ends any outage still in progress.
+pub(crate) fn schedule(outages: Vec<Outage>, targets: Vec<OutageTarget>) -> Worker<()> {
+    Worker::spawn("outage schedule", move |stop| {
+        // (at, outage, starts), ends first so that back to back outages do
+        // not overlap
+        let mut events: Vec<_> = outages
+            .iter()
+            .enumerate()
+            .flat_map(|(i, outage)| {
+                let window = outage.window();
+                [(window.start, i, true), (window.end, i, false)]
+            })
+            .collect();
+        events.sort_by_key(|(at, _, starts)| (*at, *starts));
+
+        let mut active = vec![false; outages.len()];
+        let mut down = vec![false; targets.len()];
+        let mut res = Ok(());
+        for (at, i, starts) in events {
+            if !stop.sleep_until(at) {
+                break;
+            }
+
+            active[i] = starts;
+            res = set(&targets, &mut down, |target| {
+                outages
+                    .iter()
+                    .zip(&active)
+                    .any(|(outage, active)| *active && outage.covers(target.forward))
+            });
+            if res.is_err() {
+                break;
+            }
+        }
+
+        // restore the netem qdiscs for the next run
+        let restored = set(&targets, &mut down, |_| false);
+        res.and(restored)
+    })
+}
+
+/// Take down the targets for which `down_if` is true and bring up the rest,
+/// changing only the qdiscs whose state differs.
+fn set(
+    targets: &[OutageTarget],
+    down: &mut [bool],
+    down_if: impl Fn(&OutageTarget) -> bool,
+) -> Result<()> {
+    for (target, down) in targets.iter().zip(down) {
+        let want = down_if(target);
+        if want == *down {
+            continue;
+        }
+
+        if want {
+            let blackhole = Netem {
+                loss: LossModel::Random(Probability {
+                    pct: 100.0,
+                    correlation_pct: None,
+                }),
+                ..target.netem.clone()
+            };
+            tc::netem_change(&target.ns, &target.dev, target.delay_ms, &blackhole)?;
+        } else {
+            tc::netem_change(&target.ns, &target.dev, target.delay_ms, &target.netem)?;
+        }
+        *down = want;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_outage() {
+        assert_eq!(
+            "2000:500".parse::<Outage>(),
+            Ok(Outage {
+                start: Duration::from_millis(2000),
+                duration: Duration::from_millis(500),
+                direction: OutageDirection::Both,
+            })
+        );
+        assert_eq!(
+            "0:1000:reverse".parse::<Outage>().map(|o| o.direction),
+            Ok(OutageDirection::Reverse)
+        );
+        assert!("2000".parse::<Outage>().is_err());
+        assert!("2000:500:up".parse::<Outage>().is_err());
+        assert!("2000:500:forward:1".parse::<Outage>().is_err());
+    }
+}
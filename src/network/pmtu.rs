@@ -0,0 +1,122 @@
+use std::str::FromStr;
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::{
+            error::{Result, TopologyError},
+            network::{
+                netlink::{self, Netlink},
+                nft,
+                topology::Topology,
+            },
+        };
+    }
+}
+
+/// The MTU of both ends of the link with the interface `iface`, e.g. to
+/// emulate a tunnel or VPN on that hop.
+///
+/// `IFACE:BYTES`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkMtu {
+    pub iface: String,
+    pub mtu: u32,
+}
+
+impl FromStr for LinkMtu {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (iface, mtu) = s
+            .split_once(':')
+            .ok_or_else(|| format!("{s}: expected IFACE:BYTES"))?;
+        let mtu = mtu.parse().map_err(|err| format!("{s}: {err}"))?;
+
+        Ok(LinkMtu {
+            iface: iface.to_string(),
+            mtu,
+        })
+    }
+}
+
+/// Set the MTU of both ends of every link in `mtus`.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_mtus(topology: &Topology, mtus: &[LinkMtu]) -> Result<()> {
+    for link_mtu in mtus {
+        let link = topology
+            .links
+            .iter()
+            .find(|link| link.ends.iter().any(|iface| iface.name == link_mtu.iface))
+            .expect("validated MTU link");
+        for iface in &link.ends {
+            netlink::in_netns(&iface.node, || {
+                let nl = Netlink::new().map_err(|source| TopologyError::Connect {
+                    ns: iface.node.clone(),
+                    source,
+                })?;
+                let index =
+                    nl.link_index(&iface.name)
+                        .ok_or_else(|| TopologyError::LinkNotFound {
+                            ns: iface.node.clone(),
+                            link: iface.name.clone(),
+                        })?;
+                nl.set_mtu(index, link_mtu.mtu)
+                    .map_err(|source| TopologyError::SetMtu {
+                        ns: iface.node.clone(),
+                        link: iface.name.clone(),
+                        mtu: link_mtu.mtu,
+                        source,
+                    })?;
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Drop the ICMP fragmentation needed and ICMPv6 packet too big messages
+/// `ns` sends or forwards, turning it into a PMTU black hole which only
+/// DPLPMTUD gets through.
+#[cfg(target_os = "linux")]
+pub(crate) fn drop_packet_too_big(ns: &str) -> Result<()> {
+    let rules = "\t\ticmp type destination-unreachable icmp code frag-needed drop\n\
+                 \t\ticmpv6 type packet-too-big drop\n";
+    let ruleset = format!(
+        "table inet boar_pmtu {{\n\
+         \tchain forward {{\n\
+         \t\ttype filter hook forward priority filter; policy accept;\n\
+         {rules}\
+         \t}}\n\
+         \tchain output {{\n\
+         \t\ttype filter hook output priority filter; policy accept;\n\
+         {rules}\
+         \t}}\n\
+         }}\n"
+    );
+    nft::load(ns, &ruleset).map_err(|stderr| {
+        TopologyError::PacketTooBig {
+            ns: ns.to_string(),
+            stderr,
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_link_mtu() {
+        assert_eq!(
+            "veth_m2_m3:1280".parse::<LinkMtu>(),
+            Ok(LinkMtu {
+                iface: "veth_m2_m3".to_string(),
+                mtu: 1280,
+            })
+        );
+        assert!("veth_m2_m3".parse::<LinkMtu>().is_err());
+        assert!("veth_m2_m3:jumbo".parse::<LinkMtu>().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    error::{BoarError, PreflightError, Result},
+    network::{netlink::in_netns, topology::Topology},
+};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+const RTT_PROBES: u32 = 10;
+
+/// Length of the throughput burst.
+const BURST: Duration = Duration::from_secs(1);
+
+/// The burst is paced above the bottleneck rate so that a queue builds up
+/// and the receiver sees the bottleneck drain it.
+const BURST_OVERSUBSCRIPTION: u64 = 2;
+
+const DATAGRAM_BYTES: usize = 1200;
+
+/// UDP and Ethernet headers, which htb counts against its rate along with the
+/// IP header.
+const HEADER_BYTES: usize = 8 + 14;
+
+/// RTT and throughput measured between the server and a client.
+#[derive(Debug, Clone, Copy)]
+pub struct Preflight {
+    /// Minimum RTT of the probes.
+    pub rtt_ms: f64,
+    /// Server to client throughput of the burst, headers included.
+    pub rate_mbit: f64,
+}
+
+/// A namespace and the address of its first interface.
+pub(crate) struct Endpoint<'a> {
+    pub ns: &'a str,
+    pub ip: IpAddr,
+}
+
+impl<'a> Endpoint<'a> {
+    pub fn new(topology: &Topology, ns: &'a str) -> Self {
+        let iface = topology.node_ifaces(ns).next().expect("validated topology");
+        Endpoint {
+            ns,
+            ip: iface.endpoint_addr().ip,
+        }
+    }
+}
+
+/// Measure the RTT with UDP echo probes from the client, then the throughput
+/// of a UDP burst from the server.
+///
+/// `timeout` bounds the wait for a probe reply and the idle time which ends
+/// the burst.
+pub(crate) fn measure(
+    server: &Endpoint,
+    client: &Endpoint,
+    rate_mbit: u64,
+    timeout: Duration,
+) -> Result<Preflight> {
+    let rtt = rtt(server, client, timeout)?;
+    let rate_mbit = rate(server, client, rate_mbit, timeout)?;
+
+    Ok(Preflight {
+        rtt_ms: rtt.as_secs_f64() * 1000.0,
+        rate_mbit,
+    })
+}
+
+fn rtt(server: &Endpoint, client: &Endpoint, timeout: Duration) -> Result<Duration> {
+    let echo = bind(server)?;
+    let probe = bind(client)?;
+    let echo_addr = SocketAddr::new(
+        server.ip,
+        echo.local_addr().map_err(io_err(server.ns))?.port(),
+    );
+    echo.set_read_timeout(Some(Duration::from_millis(100)))
+        .map_err(io_err(server.ns))?;
+    probe
+        .set_read_timeout(Some(timeout))
+        .map_err(io_err(client.ns))?;
+    probe.connect(echo_addr).map_err(io_err(client.ns))?;
+
+    let done = AtomicBool::new(false);
+    let min_rtt = thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut buf = [0; 16];
+            while !done.load(Ordering::Relaxed) {
+                if let Ok((len, from)) = echo.recv_from(&mut buf) {
+                    let _ = echo.send_to(&buf[..len], from);
+                }
+            }
+        });
+
+        let mut min_rtt: Option<Duration> = None;
+        let mut buf = [0; 16];
+        for seq in 0..RTT_PROBES {
+            let sent = Instant::now();
+            if probe.send(&seq.to_be_bytes()).is_err() {
+                continue;
+            }
+            // skip late replies to earlier probes, give up on a lost one
+            while let Ok(len) = probe.recv(&mut buf) {
+                if buf[..len] == seq.to_be_bytes() {
+                    let rtt = sent.elapsed();
+                    min_rtt = Some(min_rtt.map_or(rtt, |min| min.min(rtt)));
+                    break;
+                }
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+
+        min_rtt
+    });
+
+    min_rtt.ok_or_else(|| {
+        PreflightError::NoReply {
+            ns: server.ns.to_string(),
+        }
+        .into()
+    })
+}
+
+fn rate(server: &Endpoint, client: &Endpoint, rate_mbit: u64, timeout: Duration) -> Result<f64> {
+    let sink = bind(client)?;
+    let source = bind(server)?;
+    let header_bytes = HEADER_BYTES + if client.ip.is_ipv6() { 40 } else { 20 };
+    let sink_addr = SocketAddr::new(
+        client.ip,
+        sink.local_addr().map_err(io_err(client.ns))?.port(),
+    );
+    sink.set_read_timeout(Some(timeout))
+        .map_err(io_err(client.ns))?;
+    source.connect(sink_addr).map_err(io_err(server.ns))?;
+
+    let received = thread::scope(|scope| {
+        let received = scope.spawn(|| {
+            // (first arrival, last arrival, bytes after the first)
+            let mut received: Option<(Instant, Instant, usize)> = None;
+            let mut buf = [0; DATAGRAM_BYTES];
+            let deadline = Instant::now() + BURST + timeout;
+            loop {
+                match sink.recv(&mut buf) {
+                    Ok(len) => {
+                        let now = Instant::now();
+                        match &mut received {
+                            Some((_, last, bytes)) => {
+                                *last = now;
+                                *bytes += len + header_bytes;
+                            }
+                            None => received = Some((now, now, 0)),
+                        }
+                    }
+                    // idle after the burst drained
+                    Err(_) if received.is_some() => return received,
+                    Err(_) if Instant::now() > deadline => return None,
+                    Err(_) => {}
+                }
+            }
+        });
+
+        // pace every datagram, so that a shallow buffer is not overrun by
+        // bursts
+        let wire_bits = ((DATAGRAM_BYTES + header_bytes) * 8) as f64;
+        let gap = Duration::from_secs_f64(
+            wire_bits / (rate_mbit * BURST_OVERSUBSCRIPTION * 1_000_000) as f64,
+        );
+        let datagram = [0; DATAGRAM_BYTES];
+        let start = Instant::now();
+        let mut next = start;
+        while next - start < BURST {
+            while Instant::now() < next {
+                std::hint::spin_loop();
+            }
+            // the veth queue may be full for a moment
+            let _ = source.send(&datagram);
+            next += gap;
+        }
+
+        received.join().expect("burst receiver panicked")
+    });
+
+    match received {
+        Some((first, last, bytes)) if last > first => {
+            Ok(bytes as f64 * 8.0 / (last - first).as_secs_f64() / 1_000_000.0)
+        }
+        _ => Err(PreflightError::NoReply {
+            ns: client.ns.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// A socket of the address family of `endpoint`, in its namespace.
+fn bind(endpoint: &Endpoint) -> Result<UdpSocket> {
+    let unspecified: IpAddr = if endpoint.ip.is_ipv6() {
+        Ipv6Addr::UNSPECIFIED.into()
+    } else {
+        Ipv4Addr::UNSPECIFIED.into()
+    };
+    // the socket stays in the namespace it was created in
+    in_netns(endpoint.ns, || {
+        UdpSocket::bind((unspecified, 0)).map_err(io_err(endpoint.ns))
+    })
+}
+
+fn io_err(ns: &str) -> impl Fn(io::Error) -> BoarError {
+    move |source| {
+        PreflightError::Socket {
+            ns: ns.to_string(),
+            source,
+        }
+        .into()
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    error::{ProfileError, Result},
+    network::{
+        netem::{Jitter, LossModel},
+        queue::{BufferSize, Queue},
+    },
+};
+use serde::{Deserialize, Deserializer, de};
+use std::{collections::BTreeMap, fmt::Display, fs, path::Path, str::FromStr};
+
+/// Profiles selectable without a `--profile-file`.
+const BUILTIN: &str = include_str!("../../profiles/builtin.toml");
+
+/// Named network conditions, expanding into the flags of the same name.
+///
+/// Every field is optional; flags given on the command line take precedence
+/// over the profile.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// The table the profile was read from.
+    #[serde(skip)]
+    pub name: String,
+    pub delay_ms: Option<u64>,
+    pub reverse_delay_ms: Option<u64>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub loss: Option<LossModel>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub reverse_loss: Option<LossModel>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub jitter: Option<Jitter>,
+    pub rate_mbit: Option<u64>,
+    pub reverse_rate_mbit: Option<u64>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub queue: Option<Queue>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub buffer: Option<BufferSize>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub reverse_queue: Option<Queue>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub reverse_buffer: Option<BufferSize>,
+}
+
+/// The built-in profiles, overridden and extended by those of `file`.
+pub fn catalog(file: Option<&Path>) -> Result<BTreeMap<String, Profile>> {
+    let mut profiles = parse(BUILTIN, "profiles/builtin.toml")?;
+    if let Some(path) = file {
+        let file = path.display().to_string();
+        let toml = fs::read_to_string(path).map_err(|source| ProfileError::Read {
+            file: file.clone(),
+            source,
+        })?;
+        profiles.extend(parse(&toml, &file)?);
+    }
+
+    Ok(profiles)
+}
+
+fn parse(toml: &str, file: &str) -> Result<BTreeMap<String, Profile>> {
+    let mut profiles: BTreeMap<String, Profile> =
+        toml::from_str(toml).map_err(|source| ProfileError::Parse {
+            file: file.to_string(),
+            source,
+        })?;
+    for (name, profile) in &mut profiles {
+        profile.name = name.clone();
+    }
+
+    Ok(profiles)
+}
+
+/// Parse a string with the `FromStr` of its command line flag.
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BoarError;
+
+    #[test]
+    fn builtin_profiles() {
+        let profiles = catalog(None).unwrap();
+        let names: Vec<_> = profiles.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            ["3g", "lte", "satellite", "transcontinental", "wifi"]
+        );
+
+        let lte = &profiles["lte"];
+        assert_eq!(lte.name, "lte");
+        assert_eq!((lte.rate_mbit, lte.reverse_rate_mbit), (Some(30), Some(10)));
+        assert_eq!(lte.buffer, Some(BufferSize::Bdp(2.0)));
+        assert!(lte.reverse_delay_ms.is_none());
+    }
+
+    #[test]
+    fn invalid_profile() {
+        let err = parse("[lte]\nloss = \"lots\"\n", "test.toml").unwrap_err();
+        assert!(matches!(
+            err,
+            BoarError::Profile(ProfileError::Parse { .. })
+        ));
+
+        assert!(parse("[lte]\nbandwidth = 10\n", "test.toml").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use byte_unit::Byte;
+use std::{fmt, str::FromStr};
+
+/// Packet size used to convert between packet and byte limits.
+pub(crate) const PACKET_BYTES: u64 = 1500;
+
+/// The leaf qdisc of the htb bottleneck, which holds the standing queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Queue {
+    pub kind: QueueKind,
+    /// Extra qdisc options, e.g. `target 2ms interval 40ms` for fq_codel.
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueKind {
+    Pfifo,
+    Bfifo,
+    FqCodel,
+    Pie,
+    Cake,
+    Fq,
+    /// L4S dual queue coupled AQM, only in recent kernels.
+    DualPi2,
+    Red,
+}
+
+/// Size of the bottleneck buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BufferSize {
+    /// `10800p`
+    Packets(u64),
+    /// `64kb`, `1.5mb`, ...
+    Bytes(u64),
+    /// A multiple of the bandwidth-delay product, `0.5bdp`
+    Bdp(f64),
+}
+
+impl Queue {
+    /// qdisc arguments with the buffer limit in the unit the qdisc expects.
+    pub fn args(&self, buffer: BufferSize, bdp_bytes: u64, rate_mbit: u64) -> Vec<String> {
+        let bytes = buffer.bytes(bdp_bytes);
+        let packets = bytes.div_ceil(PACKET_BYTES).max(1);
+
+        let mut args = vec![self.kind.to_string()];
+        match self.kind {
+            QueueKind::Pfifo
+            | QueueKind::FqCodel
+            | QueueKind::Pie
+            | QueueKind::Fq
+            | QueueKind::DualPi2 => args.extend(["limit".to_string(), packets.to_string()]),
+            QueueKind::Bfifo => args.extend(["limit".to_string(), bytes.to_string()]),
+            QueueKind::Cake => args.extend(["memlimit".to_string(), bytes.to_string()]),
+            QueueKind::Red => {
+                // tc-red(8): the limit a few times the max threshold, which
+                // is about three times the min threshold
+                let max = (bytes / 4).max(2 * PACKET_BYTES);
+                let min = (max / 3).max(PACKET_BYTES);
+                #[rustfmt::skip]
+                let red = [
+                    "limit", &bytes.max(4 * max).to_string(),
+                    "min", &min.to_string(),
+                    "max", &max.to_string(),
+                    "avpkt", &PACKET_BYTES.to_string(),
+                    "bandwidth", &format!("{rate_mbit}mbit"),
+                ];
+                args.extend(red.map(str::to_string));
+            }
+        }
+        args.extend(self.options.iter().cloned());
+
+        args
+    }
+
+    /// Queue used when this one is not supported by the kernel: dualpi2
+    /// falls back to ECN-marking red.
+    pub fn fallback(&self) -> Option<Queue> {
+        match self.kind {
+            QueueKind::DualPi2 => Some(Queue {
+                kind: QueueKind::Red,
+                options: vec!["ecn".to_string()],
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Queue {
+            kind: QueueKind::Pfifo,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl BufferSize {
+    pub fn bytes(&self, bdp_bytes: u64) -> u64 {
+        match *self {
+            BufferSize::Packets(packets) => packets * PACKET_BYTES,
+            BufferSize::Bytes(bytes) => bytes,
+            BufferSize::Bdp(multiple) => (bdp_bytes as f64 * multiple) as u64,
+        }
+    }
+}
+
+impl FromStr for Queue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let kind = words.next().unwrap_or_default().parse()?;
+        let options: Vec<_> = words.map(str::to_string).collect();
+        if let Some(limit) = options
+            .iter()
+            .find(|option| *option == "limit" || *option == "memlimit")
+        {
+            return Err(format!("{limit} is set with --buffer"));
+        }
+
+        Ok(Queue { kind, options })
+    }
+}
+
+impl FromStr for QueueKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pfifo" => Ok(QueueKind::Pfifo),
+            "bfifo" => Ok(QueueKind::Bfifo),
+            "fq_codel" => Ok(QueueKind::FqCodel),
+            "pie" => Ok(QueueKind::Pie),
+            "cake" => Ok(QueueKind::Cake),
+            "fq" => Ok(QueueKind::Fq),
+            "dualpi2" => Ok(QueueKind::DualPi2),
+            "red" => Ok(QueueKind::Red),
+            s => Err(format!(
+                "unknown queue {s}, expected pfifo, bfifo, fq_codel, pie, cake, fq, dualpi2 or red"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for QueueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QueueKind::Pfifo => "pfifo",
+            QueueKind::Bfifo => "bfifo",
+            QueueKind::FqCodel => "fq_codel",
+            QueueKind::Pie => "pie",
+            QueueKind::Cake => "cake",
+            QueueKind::Fq => "fq",
+            QueueKind::DualPi2 => "dualpi2",
+            QueueKind::Red => "red",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for BufferSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(multiple) = s.strip_suffix("bdp") {
+            let multiple: f64 = multiple.parse().map_err(|err| format!("{s}: {err}"))?;
+            if multiple <= 0.0 {
+                return Err(format!("{s}: expected a positive multiple"));
+            }
+            return Ok(BufferSize::Bdp(multiple));
+        }
+        if let Some(packets) = s.strip_suffix('p') {
+            let packets = packets.parse().map_err(|err| format!("{s}: {err}"))?;
+            return Ok(BufferSize::Packets(packets));
+        }
+
+        let bytes = Byte::parse_str(s, true).map_err(|err| format!("{s}: {err}"))?;
+        Ok(BufferSize::Bytes(bytes.as_u64()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_buffer_size() {
+        assert_eq!("10800p".parse(), Ok(BufferSize::Packets(10800)));
+        assert_eq!("64kb".parse(), Ok(BufferSize::Bytes(64_000)));
+        assert_eq!("0.5bdp".parse(), Ok(BufferSize::Bdp(0.5)));
+        assert!("-1bdp".parse::<BufferSize>().is_err());
+        assert!("lots".parse::<BufferSize>().is_err());
+    }
+
+    #[test]
+    fn queue_args() {
+        // 20mbit with a 100ms RTT
+        let bdp_bytes = 250_000;
+
+        let rate_mbit = 20;
+
+        let pfifo: Queue = "pfifo".parse().unwrap();
+        assert_eq!(
+            pfifo
+                .args(BufferSize::Bdp(2.0), bdp_bytes, rate_mbit)
+                .join(" "),
+            "pfifo limit 334"
+        );
+
+        let bfifo: Queue = "bfifo".parse().unwrap();
+        assert_eq!(
+            bfifo
+                .args(BufferSize::Packets(10), bdp_bytes, rate_mbit)
+                .join(" "),
+            "bfifo limit 15000"
+        );
+
+        let fq_codel: Queue = "fq_codel noecn target 2ms".parse().unwrap();
+        assert_eq!(
+            fq_codel
+                .args(BufferSize::Bytes(15_000), bdp_bytes, rate_mbit)
+                .join(" "),
+            "fq_codel limit 10 noecn target 2ms"
+        );
+
+        let dualpi2: Queue = "dualpi2".parse().unwrap();
+        let red = dualpi2.fallback().unwrap();
+        assert_eq!(
+            red.args(BufferSize::Bdp(1.0), bdp_bytes, rate_mbit)
+                .join(" "),
+            "red limit 250000 min 20833 max 62500 avpkt 1500 bandwidth 20mbit ecn"
+        );
+
+        assert!("fq_codel limit 10".parse::<Queue>().is_err());
+        assert!("sfq".parse::<Queue>().is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{
+    error::{BoarError, Result},
+    network::netlink::NETNS_DIR,
+};
+use nix::{
+    mount::{MsFlags, mount},
+    sched::{CloneFlags, unshare},
+    unistd::{getgid, getuid},
+};
+use std::{fs, io};
+
+/// Holds the named namespaces, hidden behind a private tmpfs.
+const RUN_DIR: &str = "/run";
+
+/// Move boar into a new user, network and mount namespace in which it is
+/// root, so that the topology can be built without real root.
+///
+/// The topology is created below the new network namespace, and its named
+/// namespaces live on a tmpfs only mounted in the new mount namespace, so
+/// neither is visible to, nor outlives, boar. The kernel only moves a
+/// single-threaded process into a new user namespace, so this must run
+/// before any thread is spawned.
+pub(crate) fn enter() -> Result<()> {
+    let (uid, gid) = (getuid(), getgid());
+    let err = |step| move |source| BoarError::Rootless { step, source };
+
+    let flags = CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNET | CloneFlags::CLONE_NEWNS;
+    unshare(flags)
+        .map_err(io::Error::from)
+        .map_err(err("unshare"))?;
+    // without privileges in the parent namespace only our own ids can be
+    // mapped, and only once setgroups is denied
+    fs::write("/proc/self/setgroups", "deny").map_err(err("setgroups"))?;
+    fs::write("/proc/self/uid_map", format!("0 {uid} 1")).map_err(err("uid_map"))?;
+    fs::write("/proc/self/gid_map", format!("0 {gid} 1")).map_err(err("gid_map"))?;
+
+    // keep the tmpfs from propagating back
+    mount(
+        None::<&str>,
+        "/",
+        None::<&str>,
+        MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+        None::<&str>,
+    )
+    .map_err(io::Error::from)
+    .map_err(err("private mounts"))?;
+    mount(
+        Some("tmpfs"),
+        RUN_DIR,
+        Some("tmpfs"),
+        MsFlags::empty(),
+        None::<&str>,
+    )
+    .map_err(io::Error::from)
+    .map_err(err("mount /run"))?;
+    fs::create_dir(NETNS_DIR).map_err(err("create /run/netns"))?;
+
+    Ok(())
+}
@@ -0,0 +1,308 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::{
+            error::{BoarError, Result, TopologyError},
+            network::netlink,
+        };
+        use std::{
+            fs::{self, File, OpenOptions, TryLockError},
+            io,
+        };
+    }
+}
+
+/// Recorded in the report besides the sysctls of the profile: the socket
+/// buffer limits which silently cap the throughput of QUIC, and the host
+/// queueing defaults.
+#[cfg(target_os = "linux")]
+const SNAPSHOT: [&str; 13] = [
+    "net.core.rmem_max",
+    "net.core.wmem_max",
+    "net.core.rmem_default",
+    "net.core.wmem_default",
+    "net.core.optmem_max",
+    "net.core.default_qdisc",
+    "net.core.netdev_max_backlog",
+    "net.ipv4.udp_mem",
+    "net.ipv4.udp_rmem_min",
+    "net.ipv4.udp_wmem_min",
+    "net.ipv4.tcp_notsent_lowat",
+    "net.ipv4.ip_forward",
+    "net.ipv6.conf.all.forwarding",
+];
+
+/// Namespace the host-wide sysctls are recorded under in a snapshot.
+pub const HOST: &str = "host";
+
+/// Locked by the run which changed the host-wide sysctls until it restored
+/// them, so that a concurrent run does not save its values as the original
+/// ones. The kernel releases the lock when boar dies.
+#[cfg(target_os = "linux")]
+const HOST_LOCK: &str = "/run/boar_host_sysctls.lock";
+
+/// Sysctl values by namespace, with the host-wide ones under `HOST`.
+pub type SysctlSnapshot = BTreeMap<String, BTreeMap<String, String>>;
+
+/// `KEY=VALUE`, e.g. `net.ipv4.udp_rmem_min=8192`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sysctl {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for Sysctl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("{s}: expected KEY=VALUE"))?;
+        if key.is_empty() || key.contains('/') {
+            return Err(format!("{s}: expected a dotted key"));
+        }
+
+        Ok(Sysctl {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// The sysctls boar sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysctlProfile {
+    /// Written in every namespace of the topology, and gone with it.
+    pub namespace: Vec<Sysctl>,
+    /// Written in the root namespace and restored when the network is torn
+    /// down. Limits such as `net.core.rmem_max` are host-wide and read-only
+    /// inside a namespace.
+    pub host: Vec<Sysctl>,
+}
+
+impl SysctlProfile {
+    /// Forwarding, and a low `tcp_notsent_lowat`, in every namespace.
+    pub fn new(ipv6: bool) -> Self {
+        let mut namespace = vec![
+            ("net.ipv4.tcp_notsent_lowat", "131072"),
+            ("net.ipv4.ip_forward", "1"),
+        ];
+        if ipv6 {
+            namespace.push(("net.ipv6.conf.all.forwarding", "1"));
+        }
+        let namespace = namespace
+            .into_iter()
+            .map(|(key, value)| Sysctl {
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+            .collect();
+
+        SysctlProfile {
+            namespace,
+            host: Vec::new(),
+        }
+    }
+
+    /// The same profile with `namespace` and `host` added, replacing the
+    /// values of the same keys.
+    pub fn with(mut self, namespace: Vec<Sysctl>, host: Vec<Sysctl>) -> Self {
+        let merge = |sysctls: &mut Vec<Sysctl>, overrides: Vec<Sysctl>| {
+            for sysctl in overrides {
+                sysctls.retain(|s| s.key != sysctl.key);
+                sysctls.push(sysctl);
+            }
+        };
+        merge(&mut self.namespace, namespace);
+        merge(&mut self.host, host);
+        self
+    }
+}
+
+/// Write `sysctls` in the namespace of the calling thread.
+#[cfg(target_os = "linux")]
+pub(crate) fn write(ns: &str, sysctls: &[Sysctl]) -> Result<()> {
+    for sysctl in sysctls {
+        fs::write(path(&sysctl.key), &sysctl.value).map_err(|source| TopologyError::Sysctl {
+            ns: ns.to_string(),
+            key: sysctl.key.clone(),
+            source,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Host-wide sysctls changed by `write_host`, with the lock held until they
+/// are restored.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default)]
+pub(crate) struct HostSysctls {
+    original: Vec<Sysctl>,
+    /// None when no host-wide sysctl was changed.
+    _lock: Option<File>,
+}
+
+#[cfg(target_os = "linux")]
+impl HostSysctls {
+    /// Write back the values from before `write_host`.
+    pub fn restore(&self) -> Result<()> {
+        write(HOST, &self.original)
+    }
+}
+
+/// Write the host-wide `sysctls` and return their previous values. Nothing
+/// is left changed on failure, nor while another run holds them.
+#[cfg(target_os = "linux")]
+pub(crate) fn write_host(sysctls: &[Sysctl]) -> Result<HostSysctls> {
+    if sysctls.is_empty() {
+        return Ok(HostSysctls::default());
+    }
+    let lock = lock_host()?;
+
+    let original = sysctls
+        .iter()
+        .map(|sysctl| {
+            let value = read(&sysctl.key).map_err(|source| TopologyError::Sysctl {
+                ns: HOST.to_string(),
+                key: sysctl.key.clone(),
+                source,
+            })?;
+            Ok(Sysctl {
+                key: sysctl.key.clone(),
+                value,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Err(err) = write(HOST, sysctls) {
+        let _ = write(HOST, &original);
+        return Err(err);
+    }
+    Ok(HostSysctls {
+        original,
+        _lock: Some(lock),
+    })
+}
+
+/// Whether another run holds the host-wide sysctls.
+pub fn host_in_use() -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            matches!(
+                lock_host(),
+                Err(BoarError::Topology(TopologyError::HostSysctlsInUse { source: None, .. }))
+            )
+        } else {
+            // No network emulation on macOS
+            false
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lock_host() -> Result<File> {
+    let locked = |source| TopologyError::HostSysctlsInUse {
+        lock_file: HOST_LOCK.to_string(),
+        source,
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(HOST_LOCK)
+        .map_err(|err| locked(Some(err)))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(locked(None).into()),
+        Err(TryLockError::Error(err)) => Err(locked(Some(err)).into()),
+    }
+}
+
+/// The `SNAPSHOT` sysctls and those of `profile` in every namespace of
+/// `namespaces`, and on the host with `host`. Sysctls a namespace does not
+/// have are left out of it.
+#[cfg(target_os = "linux")]
+pub(crate) fn snapshot(
+    namespaces: &[&str],
+    profile: &SysctlProfile,
+    host: bool,
+) -> Result<SysctlSnapshot> {
+    let mut keys: Vec<&str> = SNAPSHOT.to_vec();
+    for sysctl in profile.namespace.iter().chain(&profile.host) {
+        if !keys.contains(&sysctl.key.as_str()) {
+            keys.push(&sysctl.key);
+        }
+    }
+    let values = || -> BTreeMap<String, String> {
+        keys.iter()
+            .filter_map(|key| Some((key.to_string(), read(key).ok()?)))
+            .collect()
+    };
+
+    let mut snapshot = SysctlSnapshot::new();
+    if host {
+        snapshot.insert(HOST.to_string(), values());
+    }
+    for ns in namespaces {
+        let values = netlink::in_netns(ns, || Ok(values()))?;
+        snapshot.insert(ns.to_string(), values);
+    }
+
+    Ok(snapshot)
+}
+
+/// The value of `key` in the namespace of the calling thread, with the tabs
+/// between multiple values turned into spaces.
+#[cfg(target_os = "linux")]
+fn read(key: &str) -> io::Result<String> {
+    let value = fs::read_to_string(path(key))?;
+    Ok(value.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// /proc/sys/net reflects the namespace of the calling thread
+#[cfg(target_os = "linux")]
+fn path(key: &str) -> String {
+    format!("/proc/sys/{}", key.replace('.', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sysctl_profile() {
+        let profile = SysctlProfile::new(false).with(
+            vec![
+                "net.ipv4.tcp_notsent_lowat=16384".parse().unwrap(),
+                "net.ipv4.udp_rmem_min=8192".parse().unwrap(),
+            ],
+            vec!["net.core.rmem_max=8388608".parse().unwrap()],
+        );
+        let namespace: Vec<_> = profile
+            .namespace
+            .iter()
+            .map(|s| (s.key.as_str(), s.value.as_str()))
+            .collect();
+        assert_eq!(
+            namespace,
+            [
+                ("net.ipv4.ip_forward", "1"),
+                ("net.ipv4.tcp_notsent_lowat", "16384"),
+                ("net.ipv4.udp_rmem_min", "8192"),
+            ]
+        );
+        assert_eq!(profile.host[0].key, "net.core.rmem_max");
+
+        let ipv6 = SysctlProfile::new(true);
+        assert!(
+            ipv6.namespace
+                .iter()
+                .any(|s| s.key == "net.ipv6.conf.all.forwarding")
+        );
+
+        assert!("net.core.rmem_max".parse::<Sysctl>().is_err());
+        assert!("net/core/rmem_max=1".parse::<Sysctl>().is_err());
+    }
+}
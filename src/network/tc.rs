@@ -0,0 +1,151 @@
+use crate::{
+    error::{Result, TopologyError},
+    network::netem::Netem,
+};
+use std::process::Command;
+
+/// Run `tc -n <ns> <args>` for the interface `dev`.
+fn tc(ns: &str, dev: &str, args: &[&str]) -> Result<()> {
+    tc_output(ns, dev, args).map(|_| ())
+}
+
+/// Run `tc -n <ns> <args>` for the interface `dev` and return its stdout.
+fn tc_output(ns: &str, dev: &str, args: &[&str]) -> Result<String> {
+    let res = Command::new("tc").arg("-n").arg(ns).args(args).output();
+
+    let stderr = match res {
+        Ok(res) if res.status.success() => {
+            return Ok(String::from_utf8_lossy(&res.stdout).into_owned());
+        }
+        Ok(res) => String::from_utf8_lossy(&res.stderr).trim().to_string(),
+        Err(err) => err.to_string(),
+    };
+    Err(TopologyError::Qdisc {
+        ns: ns.to_string(),
+        link: dev.to_string(),
+        stderr,
+    }
+    .into())
+}
+
+pub(crate) fn fq(ns: &str, dev: &str) -> Result<()> {
+    tc(ns, dev, &["qdisc", "add", "dev", dev, "root", "fq"])
+}
+
+/// Handle of netem qdiscs, which an htb shaper from `htb` can be attached
+/// below.
+const NETEM_HANDLE: &str = "10:";
+
+/// Where an htb shaper is attached below a netem qdisc.
+const NETEM_CHILD: &str = "10:1";
+
+/// A netem qdisc at the root of `dev`.
+pub(crate) fn netem(ns: &str, dev: &str, delay_ms: u64, netem: &Netem) -> Result<()> {
+    netem_qdisc(ns, dev, "add", delay_ms, netem)
+}
+
+/// Change the delay of a netem qdisc created by `netem`.
+pub(crate) fn netem_change(ns: &str, dev: &str, delay_ms: u64, netem: &Netem) -> Result<()> {
+    netem_qdisc(ns, dev, "change", delay_ms, netem)
+}
+
+fn netem_qdisc(ns: &str, dev: &str, op: &str, delay_ms: u64, netem: &Netem) -> Result<()> {
+    #[rustfmt::skip]
+    let mut args = vec![
+        "qdisc", op, "dev", dev, "root", "handle", NETEM_HANDLE, "netem",
+        // set limit to "infinite"
+        "limit", "1000000",
+    ];
+    let impairments = netem.args(delay_ms);
+    args.extend(impairments.iter().map(String::as_str));
+    tc(ns, dev, &args)
+}
+
+/// An htb class limited to `rate_mbit`, which needs a leaf qdisc from
+/// `htb_leaf`. With `below_netem` the shaper is attached below the netem
+/// qdisc of `dev`, which hands packets over once their delay has passed.
+pub(crate) fn htb(ns: &str, dev: &str, rate_mbit: u64, below_netem: bool) -> Result<()> {
+    let rate = format!("{rate_mbit}mbit");
+    let parent: &[&str] = if below_netem {
+        &["parent", NETEM_CHILD]
+    } else {
+        &["root"]
+    };
+    let mut root = vec!["qdisc", "add", "dev", dev];
+    root.extend(parent);
+    root.extend(["handle", "1:", "htb", "default", "99"]);
+    tc(ns, dev, &root)?;
+
+    #[rustfmt::skip]
+    let class = [
+        "class", "add", "dev", dev, "parent", "1:", "classid", "1:99",
+        "htb", "quantum", "1514", "rate", &rate, "ceil", &rate,
+    ];
+    tc(ns, dev, &class)
+}
+
+/// Drop what `dev` sends beyond `rate_mbit` and a burst of `burst_bytes`
+/// instead of queueing it, as a token bucket policer does.
+pub(crate) fn police(ns: &str, dev: &str, rate_mbit: u64, burst_bytes: u64) -> Result<()> {
+    let (rate, burst) = (format!("{rate_mbit}mbit"), burst_bytes.to_string());
+    tc(ns, dev, &["qdisc", "add", "dev", dev, "clsact"])?;
+
+    #[rustfmt::skip]
+    let filter = [
+        "filter", "add", "dev", dev, "egress", "protocol", "all",
+        "u32", "match", "u32", "0", "0",
+        "action", "police", "rate", &rate, "burst", &burst, "conform-exceed", "drop/ok",
+    ];
+    tc(ns, dev, &filter)
+}
+
+/// Attach `queue` as the leaf qdisc of the htb class created by `htb`.
+pub(crate) fn htb_leaf(ns: &str, dev: &str, queue: &[String]) -> Result<()> {
+    let mut args = vec![
+        "qdisc", "add", "dev", dev, "parent", "1:99", "handle", "99:",
+    ];
+    args.extend(queue.iter().map(String::as_str));
+    tc(ns, dev, &args)
+}
+
+/// `tc -s qdisc show` of the root qdisc, whose counters include those of its
+/// children.
+pub(crate) fn root_stats(ns: &str, dev: &str) -> Result<String> {
+    tc_output(ns, dev, &["-s", "qdisc", "show", "dev", dev, "root"])
+}
+
+/// `tc -s filter show` of the policer created by `police`, whose action
+/// statistics count the packets over the rate as overlimits.
+pub(crate) fn police_stats(ns: &str, dev: &str) -> Result<String> {
+    tc_output(ns, dev, &["-s", "filter", "show", "dev", dev, "egress"])
+}
+
+/// `tc -s qdisc show` of the htb shaper created by `htb`, whose counters
+/// include those of its leaf and not those of a netem qdisc above it.
+pub(crate) fn htb_stats(ns: &str, dev: &str) -> Result<String> {
+    tc_output(
+        ns,
+        dev,
+        &["-s", "qdisc", "show", "dev", dev, "handle", "1:"],
+    )
+}
+
+/// `tc -s qdisc show` of the htb leaf qdisc.
+pub(crate) fn htb_leaf_stats(ns: &str, dev: &str) -> Result<String> {
+    tc_output(
+        ns,
+        dev,
+        &["-s", "qdisc", "show", "dev", dev, "parent", "1:99"],
+    )
+}
+
+/// Change the rate of an htb class created by `htb`.
+pub(crate) fn htb_change(ns: &str, dev: &str, rate_kbit: u64) -> Result<()> {
+    let rate = format!("{rate_kbit}kbit");
+    #[rustfmt::skip]
+    let class = [
+        "class", "change", "dev", dev, "parent", "1:", "classid", "1:99",
+        "htb", "quantum", "1514", "rate", &rate, "ceil", &rate,
+    ];
+    tc(ns, dev, &class)
+}
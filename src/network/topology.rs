@@ -0,0 +1,892 @@
+use crate::error::{Result, TopologyError};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+    str::FromStr,
+};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::network::netlink::{self, Netlink};
+    }
+}
+
+/// Topology used when no `--topology` file is given.
+const DUMBBELL: &str = include_str!("../../topologies/dumbbell.toml");
+
+/// Linux limits interface names to 15 bytes (IFNAMSIZ - 1).
+const MAX_IFACE_NAME_LEN: usize = 15;
+
+/// Room left in interface names for the prefix of `Topology::with_prefix`.
+pub const NAME_PREFIX_LEN: usize = 5;
+
+/// The namespaces and veth pairs which make up the emulated network.
+///
+/// A topology is described in TOML, see `topologies/` for examples. Routes
+/// are not part of the description; they are derived from the links so that
+/// every node can reach every subnet via its shortest path.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Topology {
+    pub nodes: Vec<Node>,
+    pub links: Vec<Link>,
+}
+
+/// A network namespace.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Node {
+    pub name: String,
+    pub role: Role,
+}
+
+/// What runs inside a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Server,
+    Router,
+    Client,
+}
+
+/// A veth pair connecting two nodes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Link {
+    pub ends: [Iface; 2],
+}
+
+/// One end of a veth pair.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Iface {
+    pub node: String,
+    pub name: String,
+    pub addr: IfaceAddr,
+    /// IPv6 address of a dual-stack interface. Derived from `addr` when
+    /// missing, see `IpVersion`.
+    #[serde(default)]
+    pub addr6: Option<IfaceAddr>,
+    /// Egress qdisc installed on this interface.
+    #[serde(default)]
+    pub qdisc: Option<Qdisc>,
+}
+
+/// Egress qdisc of an interface.
+///
+/// The parameters come from the `NetworkSetup` so that the same topology can
+/// be reused for different network conditions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Qdisc {
+    /// Fair queueing, which also paces the server.
+    Fq,
+    /// netem with the delay and loss of the `NetworkSetup`.
+    Netem,
+    /// htb shaper with the rate of the `NetworkSetup`.
+    Htb,
+    /// netem above an htb shaper, for a hop which both delays and limits
+    /// the rate. Packets are delayed before they queue for the rate.
+    #[serde(rename = "htb+netem")]
+    HtbNetem,
+}
+
+impl Qdisc {
+    /// Whether the qdisc includes a netem with the delay and loss.
+    pub fn delays(self) -> bool {
+        matches!(self, Qdisc::Netem | Qdisc::HtbNetem)
+    }
+
+    /// Whether the qdisc includes an htb shaper with the rate.
+    pub fn shapes(self) -> bool {
+        matches!(self, Qdisc::Htb | Qdisc::HtbNetem)
+    }
+}
+
+/// Address families of the emulated network.
+///
+/// IPv6 addresses which a topology does not give are derived from the IPv4
+/// ones by embedding them in `fd00::/96`, e.g. `10.55.10.1/24` becomes
+/// `fd00::a37:a01/120`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpVersion {
+    V4,
+    V6,
+    /// IPv4 and IPv6 on every interface, with endpoints connecting over
+    /// IPv6.
+    Dual,
+}
+
+impl FromStr for IpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4" => Ok(IpVersion::V4),
+            "6" => Ok(IpVersion::V6),
+            "dual" => Ok(IpVersion::Dual),
+            _ => Err(format!("unknown IP version {s:?}, expected 4, 6 or dual")),
+        }
+    }
+}
+
+/// An interface address with its prefix length, e.g. `10.55.10.1/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IfaceAddr {
+    pub ip: IpAddr,
+    pub prefix_len: u8,
+}
+
+/// A route to `dst` via the gateway `via`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub dst: IfaceAddr,
+    pub via: IpAddr,
+}
+
+impl Topology {
+    /// The same topology with `prefix` in front of every namespace and
+    /// interface name, so that topologies with different prefixes can exist
+    /// side by side. Their subnets may overlap, as no interface of one shares
+    /// a namespace with the other.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        assert!(
+            prefix.len() <= NAME_PREFIX_LEN,
+            "prefix {prefix} is too long"
+        );
+        for node in &mut self.nodes {
+            node.name = format!("{prefix}{}", node.name);
+        }
+        for iface in self.links.iter_mut().flat_map(|link| link.ends.iter_mut()) {
+            iface.node = format!("{prefix}{}", iface.node);
+            iface.name = format!("{prefix}{}", iface.name);
+        }
+        self
+    }
+
+    /// The same topology with the addresses of `version`.
+    pub fn with_ip_version(mut self, version: IpVersion) -> Self {
+        for iface in self.links.iter_mut().flat_map(|link| link.ends.iter_mut()) {
+            let addr6 = iface.addr6.take().unwrap_or_else(|| iface.addr.mapped_v6());
+            match version {
+                IpVersion::V4 => {}
+                IpVersion::V6 => iface.addr = addr6,
+                IpVersion::Dual => iface.addr6 = Some(addr6),
+            }
+        }
+        self
+    }
+
+    /// Whether a namespace of this topology exists already, e.g. one of
+    /// another plan which drew the same prefix.
+    pub fn in_use(&self) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                self.nodes.iter().any(|node| netlink::netns_exists(&node.name))
+            } else {
+                // No network emulation on macOS
+                false
+            }
+        }
+    }
+
+    /// One server, three middle hops and two clients.
+    pub fn dumbbell() -> Self {
+        Self::parse(DUMBBELL, "topologies/dumbbell.toml").expect("valid built-in topology")
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let file = path.display().to_string();
+        let toml = fs::read_to_string(path).map_err(|source| TopologyError::Read {
+            file: file.clone(),
+            source,
+        })?;
+
+        Self::parse(&toml, &file)
+    }
+
+    fn parse(toml: &str, file: &str) -> Result<Self> {
+        let topology: Topology = toml::from_str(toml).map_err(|source| TopologyError::Parse {
+            file: file.to_string(),
+            source,
+        })?;
+        topology
+            .validate()
+            .map_err(|reason| TopologyError::Invalid {
+                file: file.to_string(),
+                reason,
+            })?;
+
+        Ok(topology)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut nodes = HashSet::new();
+        for node in &self.nodes {
+            if !nodes.insert(node.name.as_str()) {
+                return Err(format!("duplicate node {}", node.name));
+            }
+        }
+
+        let mut ifaces = HashSet::new();
+        for iface in self.ifaces() {
+            if !nodes.contains(iface.node.as_str()) {
+                return Err(format!(
+                    "{} references unknown node {}",
+                    iface.name, iface.node
+                ));
+            }
+            // leave room for the prefix
+            let max_len = MAX_IFACE_NAME_LEN - NAME_PREFIX_LEN;
+            if iface.name.len() > max_len {
+                return Err(format!(
+                    "interface name {} is longer than {max_len} bytes",
+                    iface.name
+                ));
+            }
+            if !ifaces.insert(iface.name.as_str()) {
+                return Err(format!("duplicate interface {}", iface.name));
+            }
+        }
+
+        for link in &self.links {
+            let [a, b] = &link.ends;
+            if a.node == b.node {
+                return Err(format!("{} and {} are on the same node", a.name, b.name));
+            }
+            if a.addr.network() != b.addr.network() {
+                return Err(format!(
+                    "{} and {} are on different subnets",
+                    a.addr, b.addr
+                ));
+            }
+            match (a.addr6, b.addr6) {
+                (None, None) => {}
+                (Some(a6), Some(b6)) if a6.ip.is_ipv6() && a6.network() == b6.network() => {}
+                _ => {
+                    return Err(format!(
+                        "{} and {} need IPv6 addresses on the same subnet",
+                        a.name, b.name
+                    ));
+                }
+            }
+        }
+
+        if self.server().is_none() {
+            return Err("no node with role = \"server\"".to_string());
+        }
+        if self.clients().next().is_none() {
+            return Err("no node with role = \"client\"".to_string());
+        }
+        for server in self.servers() {
+            let Some(addr) = self.node_addr(&server.name).map(|addr| addr.network()) else {
+                return Err(format!("server {} has no interface", server.name));
+            };
+            for client in self.clients() {
+                let attached = self
+                    .node_ifaces(&client.name)
+                    .any(|iface| iface.endpoint_addr().network() == addr);
+                let routed = self
+                    .routes(&client.name)
+                    .iter()
+                    .any(|route| route.dst == addr);
+                if !attached && !routed {
+                    return Err(format!("{} cannot reach {}", client.name, server.name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The first server node, which the pre-flight check and the base RTT
+    /// refer to.
+    pub fn server(&self) -> Option<&Node> {
+        self.servers().next()
+    }
+
+    pub fn servers(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(|node| node.role == Role::Server)
+    }
+
+    /// The address an endpoint in `node` is reached at.
+    pub fn node_addr(&self, node: &str) -> Option<IfaceAddr> {
+        self.node_ifaces(node).next().map(Iface::endpoint_addr)
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(|node| node.role == Role::Client)
+    }
+
+    /// Whether any interface has an IPv6 address.
+    pub fn has_ipv6(&self) -> bool {
+        self.ifaces()
+            .any(|iface| iface.addr.ip.is_ipv6() || iface.addr6.is_some())
+    }
+
+    pub fn ifaces(&self) -> impl Iterator<Item = &Iface> {
+        self.links.iter().flat_map(|link| link.ends.iter())
+    }
+
+    /// Interfaces which live in the namespace `node`.
+    pub fn node_ifaces<'a>(&'a self, node: &'a str) -> impl Iterator<Item = &'a Iface> {
+        self.ifaces().filter(move |iface| iface.node == node)
+    }
+
+    /// (local, remote) ends of every link attached to `node`.
+    fn neighbours<'a>(&'a self, node: &'a str) -> impl Iterator<Item = (&'a Iface, &'a Iface)> {
+        self.links.iter().filter_map(move |link| {
+            let [a, b] = &link.ends;
+            if a.node == node {
+                Some((a, b))
+            } else if b.node == node {
+                Some((b, a))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Egress interfaces a packet from `from` to `to` leaves through, in
+    /// order. Empty if `to` is unreachable.
+    pub fn egress_path<'a>(&'a self, from: &'a str, to: &str) -> Vec<&'a Iface> {
+        // node -> egress interface on the previous node
+        let mut prev: HashMap<&str, &Iface> = HashMap::new();
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            for (local, remote) in self.neighbours(current) {
+                if seen.insert(&remote.node) {
+                    prev.insert(&remote.node, local);
+                    queue.push_back(&remote.node);
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut node = to;
+        while let Some(iface) = prev.get(node) {
+            path.push(*iface);
+            node = &iface.node;
+        }
+        path.reverse();
+
+        path
+    }
+
+    /// Whether packets leaving `iface` travel from a server towards a
+    /// client, i.e. in the direction of the download. Interfaces on no
+    /// server to client path count as reverse.
+    pub fn is_forward(&self, iface: &Iface) -> bool {
+        self.servers().any(|server| {
+            self.clients().any(|client| {
+                self.egress_path(&server.name, &client.name)
+                    .into_iter()
+                    .any(|hop| hop.node == iface.node && hop.name == iface.name)
+            })
+        })
+    }
+
+    /// Routes `node` needs to reach every subnet it is not attached to, of
+    /// both address families on a dual-stack topology.
+    ///
+    /// The gateway for a subnet is the first hop of the shortest path towards
+    /// the nearest end of the link carrying that subnet.
+    pub fn routes(&self, node: &str) -> Vec<Route> {
+        // node -> (distance, remote end of the first hop)
+        let mut paths: HashMap<&str, (usize, Option<&Iface>)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        paths.insert(node, (0, None));
+        queue.push_back(node);
+
+        while let Some(current) = queue.pop_front() {
+            let (dist, gateway) = paths[current];
+            for (_, remote) in self.neighbours(current) {
+                if paths.contains_key(remote.node.as_str()) {
+                    continue;
+                }
+                let gateway = gateway.or(Some(remote));
+                paths.insert(&remote.node, (dist + 1, gateway));
+                queue.push_back(&remote.node);
+            }
+        }
+
+        let mut routes = Vec::new();
+        for link in &self.links {
+            let [a, b] = &link.ends;
+            if a.node == node || b.node == node {
+                // directly attached
+                continue;
+            }
+
+            let nearest = [a, b]
+                .into_iter()
+                .filter_map(|end| paths.get(end.node.as_str()))
+                .min_by_key(|(dist, _)| *dist);
+            if let Some((_, Some(gateway))) = nearest {
+                routes.push(Route {
+                    dst: a.addr.network(),
+                    via: gateway.addr.ip,
+                });
+                if let (Some(dst), Some(via)) = (a.addr6, gateway.addr6) {
+                    routes.push(Route {
+                        dst: dst.network(),
+                        via: via.ip,
+                    });
+                }
+            }
+        }
+
+        routes
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Topology {
+    /// Delete the namespaces of this topology, along with any of its veths
+    /// left in the root namespace by a setup which failed halfway. Objects
+    /// of other topologies are left alone.
+    pub fn cleanup(&self) -> Result<()> {
+        for node in &self.nodes {
+            if netlink::netns_exists(&node.name) {
+                netlink::del_netns(&node.name).map_err(|source| {
+                    TopologyError::DeleteNamespace {
+                        ns: node.name.clone(),
+                        source,
+                    }
+                })?;
+            }
+        }
+
+        let root = Netlink::new().map_err(|source| TopologyError::Connect {
+            ns: "root".to_string(),
+            source,
+        })?;
+        for iface in self.ifaces() {
+            if let Some(index) = root.link_index(&iface.name) {
+                root.del_link(index)
+                    .map_err(|source| TopologyError::DeleteLink {
+                        link: iface.name.clone(),
+                        source,
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the namespaces. If one fails, e.g. because it belongs to
+    /// another topology with the same prefix, those created before it are
+    /// deleted again and the others are left alone.
+    pub fn create_namespaces(&self) -> Result<()> {
+        for (i, node) in self.nodes.iter().enumerate() {
+            if let Err(source) = netlink::add_netns(&node.name) {
+                for created in &self.nodes[..i] {
+                    let _ = netlink::del_netns(&created.name);
+                }
+                return Err(TopologyError::AddNamespace {
+                    ns: node.name.clone(),
+                    source,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the veth pairs, addresses and routes in the namespaces made by
+    /// `create_namespaces`.
+    pub fn create(&self) -> Result<()> {
+        // Create veth pairs in the root namespace and move each end to its node
+        let root = Netlink::new().map_err(|source| TopologyError::Connect {
+            ns: "root".to_string(),
+            source,
+        })?;
+        for link in &self.links {
+            let [a, b] = &link.ends;
+            root.add_veth(&a.name, &b.name)
+                .map_err(|source| TopologyError::AddVeth {
+                    link: a.name.clone(),
+                    peer: b.name.clone(),
+                    source,
+                })?;
+
+            for iface in &link.ends {
+                let index =
+                    root.link_index(&iface.name)
+                        .ok_or_else(|| TopologyError::LinkNotFound {
+                            ns: "root".to_string(),
+                            link: iface.name.clone(),
+                        })?;
+                root.move_link(index, &iface.node)
+                    .map_err(|source| TopologyError::MoveLink {
+                        link: iface.name.clone(),
+                        ns: iface.node.clone(),
+                        source,
+                    })?;
+            }
+        }
+
+        for node in &self.nodes {
+            netlink::in_netns(&node.name, || self.configure_node(&node.name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Configure addresses and routes. Must run inside `node`.
+    fn configure_node(&self, node: &str) -> Result<()> {
+        let nl = Netlink::new().map_err(|source| TopologyError::Connect {
+            ns: node.to_string(),
+            source,
+        })?;
+        let link_index = |link: &str| {
+            nl.link_index(link)
+                .ok_or_else(|| TopologyError::LinkNotFound {
+                    ns: node.to_string(),
+                    link: link.to_string(),
+                })
+        };
+        let link_up = |index, link: &str| {
+            nl.link_up(index).map_err(|source| TopologyError::LinkUp {
+                ns: node.to_string(),
+                link: link.to_string(),
+                source,
+            })
+        };
+
+        link_up(link_index("lo")?, "lo")?;
+        for iface in self.node_ifaces(node) {
+            let index = link_index(&iface.name)?;
+            for addr in [Some(iface.addr), iface.addr6].into_iter().flatten() {
+                nl.add_addr(index, addr)
+                    .map_err(|source| TopologyError::AddAddress {
+                        ns: node.to_string(),
+                        link: iface.name.clone(),
+                        addr,
+                        source,
+                    })?;
+            }
+            link_up(index, &iface.name)?;
+        }
+
+        for route in self.routes(node) {
+            nl.add_route(route)
+                .map_err(|source| TopologyError::AddRoute {
+                    ns: node.to_string(),
+                    route,
+                    source,
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Iface {
+    /// The address endpoints use, the IPv6 one of a dual-stack interface.
+    pub fn endpoint_addr(&self) -> IfaceAddr {
+        self.addr6.unwrap_or(self.addr)
+    }
+}
+
+impl IfaceAddr {
+    /// The IPv4 address embedded in `fd00::/96`, or the address itself if it
+    /// is IPv6.
+    pub fn mapped_v6(&self) -> IfaceAddr {
+        match self.ip {
+            IpAddr::V4(ip) => IfaceAddr {
+                ip: IpAddr::V6(Ipv6Addr::from((0xfd00_u128 << 112) | u32::from(ip) as u128)),
+                prefix_len: self.prefix_len + 96,
+            },
+            IpAddr::V6(_) => *self,
+        }
+    }
+
+    /// The address with the host bits cleared, e.g. `10.55.10.0/24`.
+    pub fn network(&self) -> IfaceAddr {
+        let ip = match self.ip {
+            IpAddr::V4(ip) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+            }
+            IpAddr::V6(ip) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+            }
+        };
+
+        IfaceAddr {
+            ip,
+            prefix_len: self.prefix_len,
+        }
+    }
+
+    /// The highest address of the subnet below the IPv4 broadcast address,
+    /// e.g. `10.55.40.254/24`.
+    pub fn last_host(&self) -> IfaceAddr {
+        let ip = match self.network().ip {
+            IpAddr::V4(ip) => {
+                let hosts = u32::MAX.checked_shr(self.prefix_len as u32).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(ip) + hosts.saturating_sub(1)))
+            }
+            IpAddr::V6(ip) => {
+                let hosts = u128::MAX.checked_shr(self.prefix_len as u32).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) + hosts.saturating_sub(1)))
+            }
+        };
+
+        IfaceAddr {
+            ip,
+            prefix_len: self.prefix_len,
+        }
+    }
+}
+
+impl FromStr for IfaceAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix_len) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected <ip>/<prefix_len>, got {s}"))?;
+        let ip: IpAddr = ip.parse().map_err(|err| format!("{s}: {err}"))?;
+        let prefix_len: u8 = prefix_len.parse().map_err(|err| format!("{s}: {err}"))?;
+
+        let max_len = if ip.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            return Err(format!("{s}: prefix length exceeds {max_len}"));
+        }
+
+        Ok(IfaceAddr { ip, prefix_len })
+    }
+}
+
+impl TryFrom<String> for IfaceAddr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for IfaceAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.ip, self.prefix_len)
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} via {}", self.dst, self.via)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BoarError;
+
+    fn route(dst: &str, via: &str) -> Route {
+        Route {
+            dst: dst.parse().unwrap(),
+            via: via.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn iface_addr_network() {
+        let addr: IfaceAddr = "10.55.41.2/24".parse().unwrap();
+        assert_eq!(addr.network(), "10.55.41.0/24".parse().unwrap());
+        assert_eq!(addr.last_host(), "10.55.41.254/24".parse().unwrap());
+
+        let addr: IfaceAddr = "fd00:55:10::1/64".parse().unwrap();
+        assert_eq!(addr.network(), "fd00:55:10::/64".parse().unwrap());
+
+        assert!("10.55.41.2".parse::<IfaceAddr>().is_err());
+        assert!("10.55.41.2/33".parse::<IfaceAddr>().is_err());
+    }
+
+    #[test]
+    fn shipped_topologies() {
+        for entry in fs::read_dir("topologies").unwrap() {
+            let path = entry.unwrap().path();
+            let topology = Topology::from_file(&path).unwrap();
+            for server in topology.servers() {
+                let addr = topology.node_addr(&server.name);
+                assert!(addr.is_some(), "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_topology() {
+        let toml = r#"
+            [[nodes]]
+            name = "ns_s1"
+            role = "server"
+
+            [[nodes]]
+            name = "ns_c1"
+            role = "client"
+
+            [[links]]
+            ends = [
+                { node = "ns_s1", name = "veth_s1_c1", addr = "10.55.10.1/24" },
+                { node = "ns_c1", name = "veth_c1_s1", addr = "10.55.20.2/24" },
+            ]
+        "#;
+
+        let err = Topology::parse(toml, "test.toml").unwrap_err();
+        assert!(matches!(
+            err,
+            BoarError::Topology(TopologyError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn dumbbell_client_routes() {
+        let topology = Topology::dumbbell();
+
+        let routes = topology.routes("ns_c1");
+        assert_eq!(
+            routes,
+            vec![
+                route("10.55.10.0/24", "10.55.41.1"),
+                route("10.55.20.0/24", "10.55.41.1"),
+                route("10.55.30.0/24", "10.55.41.1"),
+                route("10.55.42.0/24", "10.55.41.1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dumbbell_ip_versions() {
+        let addr: IfaceAddr = "10.55.41.1/24".parse().unwrap();
+        assert_eq!(addr.mapped_v6(), "fd00::a37:2901/120".parse().unwrap());
+
+        let topology = Topology::dumbbell().with_ip_version(IpVersion::V6);
+        assert_eq!(
+            topology.node_addr("ns_s1"),
+            Some("fd00::a37:a01/120".parse().unwrap())
+        );
+        assert_eq!(
+            topology.routes("ns_c1")[0],
+            route("fd00::a37:a00/120", "fd00::a37:2901")
+        );
+
+        let topology = Topology::dumbbell().with_ip_version(IpVersion::Dual);
+        topology.validate().unwrap();
+        assert_eq!(
+            topology.node_addr("ns_s1"),
+            Some("fd00::a37:a01/120".parse().unwrap())
+        );
+        let routes = topology.routes("ns_c1");
+        assert_eq!(routes.len(), 8);
+        assert_eq!(
+            routes[..2],
+            [
+                route("10.55.10.0/24", "10.55.41.1"),
+                route("fd00::a37:a00/120", "fd00::a37:2901"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dumbbell_with_prefix() {
+        let topology = Topology::dumbbell().with_prefix("1a2b_");
+        assert_eq!(topology.server().unwrap().name, "1a2b_ns_s1");
+
+        let path: Vec<_> = topology
+            .egress_path("1a2b_ns_s1", "1a2b_ns_c1")
+            .into_iter()
+            .map(|iface| iface.name.as_str())
+            .collect();
+        assert_eq!(path[0], "1a2b_veth_s1_m1");
+        assert!(path.iter().all(|name| name.len() <= MAX_IFACE_NAME_LEN));
+    }
+
+    #[test]
+    fn dumbbell_egress_path() {
+        let topology = Topology::dumbbell();
+
+        let path: Vec<_> = topology
+            .egress_path("ns_s1", "ns_c1")
+            .into_iter()
+            .map(|iface| iface.name.as_str())
+            .collect();
+        assert_eq!(
+            path,
+            ["veth_s1_m1", "veth_m1_m2", "veth_m2_m3", "veth_m3_c1"]
+        );
+
+        let path: Vec<_> = topology
+            .egress_path("ns_c2", "ns_s1")
+            .into_iter()
+            .map(|iface| iface.name.as_str())
+            .collect();
+        assert_eq!(
+            path,
+            ["veth_c2_m3", "veth_m3_m2", "veth_m2_m1", "veth_m1_s1"]
+        );
+    }
+
+    #[test]
+    fn dumbbell_forward_ifaces() {
+        let topology = Topology::dumbbell();
+
+        let forward: Vec<_> = topology
+            .ifaces()
+            .filter(|iface| iface.qdisc.is_some() && topology.is_forward(iface))
+            .map(|iface| iface.name.as_str())
+            .collect();
+        assert_eq!(forward, ["veth_s1_m1", "veth_m1_m2", "veth_m2_m3"]);
+    }
+
+    #[test]
+    fn two_servers_share_bottleneck() {
+        let topology = Topology::from_file(Path::new("topologies/two_servers.toml")).unwrap();
+        assert_eq!(topology.servers().count(), 2);
+
+        let hops = |server| -> Vec<_> {
+            topology
+                .egress_path(server, "ns_c1")
+                .into_iter()
+                .skip(1)
+                .map(|iface| iface.name.as_str())
+                .collect()
+        };
+        assert_eq!(hops("ns_s1"), hops("ns_s2"));
+
+        let forward: Vec<_> = topology
+            .ifaces()
+            .filter(|iface| iface.qdisc == Some(Qdisc::Fq) && topology.is_forward(iface))
+            .map(|iface| iface.name.as_str())
+            .collect();
+        assert_eq!(forward, ["veth_s1_m1", "veth_s2_m1"]);
+    }
+
+    #[test]
+    fn dumbbell_middle_routes() {
+        let topology = Topology::dumbbell();
+
+        let routes = topology.routes("ns_m2");
+        assert_eq!(
+            routes,
+            vec![
+                route("10.55.10.0/24", "10.55.20.1"),
+                route("10.55.41.0/24", "10.55.30.2"),
+                route("10.55.42.0/24", "10.55.30.2"),
+            ]
+        );
+    }
+}
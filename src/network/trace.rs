@@ -0,0 +1,306 @@
+use crate::{
+    error::{Result, TraceError},
+    network::{
+        Conditions, tc,
+        worker::{StopSignal, Worker},
+    },
+};
+use std::{fmt, fs, path::Path, time::Duration};
+
+/// Size of a Mahimahi delivery opportunity.
+const MAHIMAHI_MTU_BITS: u64 = 1500 * 8;
+
+/// htb refuses a rate of zero, so empty Mahimahi bins are clamped to this.
+const MIN_RATE_KBIT: u64 = 1;
+
+/// A bandwidth/delay schedule replayed on the bottleneck during each run.
+///
+/// Two formats are supported:
+/// - CSV with `time_ms,rate_mbit,delay_ms` rows, where `delay_ms` may be left
+///   empty to keep the current delay. The last row holds until the run ends.
+/// - Mahimahi, where every line is the timestamp (ms) of one MTU-sized
+///   delivery opportunity. Opportunities are binned into rates and the trace
+///   loops, as it does in Mahimahi.
+#[derive(Clone)]
+pub struct Trace {
+    file: String,
+    steps: Vec<TraceStep>,
+    /// Length of one loop for looping traces.
+    period: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    pub at: Duration,
+    pub rate_kbit: u64,
+    pub delay_ms: Option<u64>,
+}
+
+impl Trace {
+    /// Load a trace, picking the format by extension: `.csv` is CSV and
+    /// anything else is Mahimahi.
+    pub fn from_file(path: &Path, bin: Duration) -> Result<Self> {
+        let file = path.display().to_string();
+        let content = fs::read_to_string(path).map_err(|source| TraceError::Read {
+            file: file.clone(),
+            source,
+        })?;
+
+        let trace = if path.extension().is_some_and(|ext| ext == "csv") {
+            Self::from_csv(&content)
+        } else {
+            Self::from_mahimahi(&content, bin)
+        };
+
+        trace
+            .map(|(steps, period)| Trace {
+                file: file.clone(),
+                steps,
+                period,
+            })
+            .map_err(|reason| TraceError::Invalid { file, reason }.into())
+    }
+
+    fn from_csv(content: &str) -> Result<(Vec<TraceStep>, Option<Duration>), String> {
+        let mut steps: Vec<TraceStep> = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<_> = line.split(',').map(str::trim).collect();
+            let Ok(at) = fields[0].parse::<u64>() else {
+                if steps.is_empty() {
+                    // header
+                    continue;
+                }
+                return Err(format!("line {}: invalid time {:?}", i + 1, fields[0]));
+            };
+            let rate_mbit = fields
+                .get(1)
+                .and_then(|rate| rate.parse::<f64>().ok())
+                .filter(|rate| *rate > 0.0)
+                .ok_or_else(|| format!("line {}: expected a positive rate_mbit", i + 1))?;
+            let delay_ms = match fields.get(2) {
+                None | Some(&"") => None,
+                Some(delay) => Some(
+                    delay
+                        .parse::<u64>()
+                        .map_err(|err| format!("line {}: delay_ms: {err}", i + 1))?,
+                ),
+            };
+
+            let at = Duration::from_millis(at);
+            if steps.last().is_some_and(|prev| prev.at > at) {
+                return Err(format!("line {}: time goes backwards", i + 1));
+            }
+            steps.push(TraceStep {
+                at,
+                rate_kbit: ((rate_mbit * 1000.0) as u64).max(MIN_RATE_KBIT),
+                delay_ms,
+            });
+        }
+
+        if steps.is_empty() {
+            return Err("empty trace".to_string());
+        }
+        Ok((steps, None))
+    }
+
+    fn from_mahimahi(
+        content: &str,
+        bin: Duration,
+    ) -> Result<(Vec<TraceStep>, Option<Duration>), String> {
+        let bin_ms = bin.as_millis() as u64;
+        if bin_ms == 0 {
+            return Err("bin must be at least 1ms".to_string());
+        }
+
+        let mut opportunities = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let ms = line
+                .parse::<u64>()
+                .map_err(|err| format!("line {}: {err}", i + 1))?;
+            if opportunities.last().is_some_and(|prev| *prev > ms) {
+                return Err(format!("line {}: time goes backwards", i + 1));
+            }
+            opportunities.push(ms);
+        }
+        let Some(&period_ms) = opportunities.last() else {
+            return Err("empty trace".to_string());
+        };
+        let period_ms = period_ms.max(1);
+
+        let bins = period_ms.div_ceil(bin_ms);
+        let mut counts = vec![0u64; bins as usize];
+        for ms in opportunities {
+            // the last opportunity marks the end of the loop
+            let idx = (ms / bin_ms).min(bins - 1);
+            counts[idx as usize] += 1;
+        }
+
+        let steps = counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| TraceStep {
+                at: Duration::from_millis(i as u64 * bin_ms),
+                // bits per ms is kbit per s
+                rate_kbit: (count * MAHIMAHI_MTU_BITS / bin_ms).max(MIN_RATE_KBIT),
+                delay_ms: None,
+            })
+            .collect();
+
+        Ok((steps, Some(Duration::from_millis(period_ms))))
+    }
+}
+
+impl fmt::Debug for Trace {
+    // A trace can have thousands of steps, so only summarise it in reports.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rates = self.steps.iter().map(|step| step.rate_kbit);
+        f.debug_struct("Trace")
+            .field("file", &self.file)
+            .field("steps", &self.steps.len())
+            .field("period", &self.period)
+            .field("min_rate_kbit", &rates.clone().min())
+            .field("max_rate_kbit", &rates.max())
+            .finish()
+    }
+}
+
+/// A qdisc changed by a trace `replay`.
+#[derive(Debug, Clone)]
+pub(crate) struct TraceTarget {
+    pub ns: String,
+    pub dev: String,
+    /// Conditions of the interface, restored when the replay stops. Its
+    /// impairments are kept while the replay runs.
+    pub base: Conditions,
+}
+
+/// Interfaces whose qdiscs are changed by a trace `replay`.
+#[derive(Debug, Clone)]
+pub(crate) struct TraceTargets {
+    /// htb shapers, which take the rates of the trace.
+    pub htb: Vec<TraceTarget>,
+    /// netem qdiscs, which take the delays of the trace.
+    pub netem: Vec<TraceTarget>,
+}
+
+/// Start replaying `trace` on `targets`, with every run starting from the
+/// beginning of the trace. Stopping the replay restores the base conditions
+/// of the targets.
+pub(crate) fn replay(trace: Trace, targets: TraceTargets) -> Worker<()> {
+    Worker::spawn("trace replay", move |stop| {
+        let res = replay_steps(&trace, &targets, &stop);
+        // restore the qdiscs for the next run
+        let restored = restore(&targets);
+        res.and(restored)
+    })
+}
+
+/// Apply the steps of `trace` on time until the replay is stopped.
+fn replay_steps(trace: &Trace, targets: &TraceTargets, stop: &StopSignal) -> Result<()> {
+    let mut offset = Duration::ZERO;
+    loop {
+        for step in &trace.steps {
+            if !stop.sleep_until(offset + step.at) {
+                return Ok(());
+            }
+            apply(step, targets)?;
+        }
+
+        match trace.period {
+            Some(period) => offset += period,
+            None => {
+                // hold the last step until stopped
+                stop.wait();
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn apply(step: &TraceStep, targets: &TraceTargets) -> Result<()> {
+    for target in &targets.htb {
+        tc::htb_change(&target.ns, &target.dev, step.rate_kbit)?;
+    }
+    if let Some(delay_ms) = step.delay_ms {
+        for target in &targets.netem {
+            tc::netem_change(&target.ns, &target.dev, delay_ms, &target.base.netem)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Bring every target back to its base rate and delay.
+fn restore(targets: &TraceTargets) -> Result<()> {
+    for target in &targets.htb {
+        tc::htb_change(&target.ns, &target.dev, target.base.rate_mbit * 1000)?;
+    }
+    for target in &targets.netem {
+        let base = &target.base;
+        tc::netem_change(&target.ns, &target.dev, base.delay_ms, &base.netem)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_trace() {
+        let csv = "time_ms,rate_mbit,delay_ms\n\
+                   0,20,50\n\
+                   # capacity drop\n\
+                   2000,2.5,\n\
+                   4000,10,100\n";
+
+        let (steps, period) = Trace::from_csv(csv).unwrap();
+        assert_eq!(period, None);
+        assert_eq!(
+            steps,
+            vec![
+                TraceStep {
+                    at: Duration::from_millis(0),
+                    rate_kbit: 20_000,
+                    delay_ms: Some(50),
+                },
+                TraceStep {
+                    at: Duration::from_millis(2000),
+                    rate_kbit: 2_500,
+                    delay_ms: None,
+                },
+                TraceStep {
+                    at: Duration::from_millis(4000),
+                    rate_kbit: 10_000,
+                    delay_ms: Some(100),
+                },
+            ]
+        );
+
+        assert!(Trace::from_csv("0,20\n10,0\n").is_err());
+        assert!(Trace::from_csv("10,20\n0,20\n").is_err());
+    }
+
+    #[test]
+    fn mahimahi_trace() {
+        // 0-99ms: 10 opportunities, 100-199ms: none, 200-299ms: 1
+        let mut lines: Vec<_> = (0..10).map(|ms| ms.to_string()).collect();
+        lines.push("250".to_string());
+        let trace = lines.join("\n");
+
+        let (steps, period) = Trace::from_mahimahi(&trace, Duration::from_millis(100)).unwrap();
+        assert_eq!(period, Some(Duration::from_millis(250)));
+        let rates: Vec<_> = steps.iter().map(|step| step.rate_kbit).collect();
+        assert_eq!(rates, vec![1200, MIN_RATE_KBIT, 120]);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::error::Result;
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// A thread acting on the network during a client run, such as a trace
+/// replay, an outage schedule or the qdisc monitor. It must be stopped once
+/// the run completes.
+pub struct Worker<T> {
+    name: &'static str,
+    stop: Sender<()>,
+    handle: JoinHandle<Result<T>>,
+}
+
+impl<T: Send + 'static> Worker<T> {
+    /// Run `work` on its own thread until it returns. `work` is expected to
+    /// wait on the `StopSignal` it is given and to wind down once stopped.
+    pub(crate) fn spawn(
+        name: &'static str,
+        work: impl FnOnce(StopSignal) -> Result<T> + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            work(StopSignal {
+                stopped,
+                start: Instant::now(),
+            })
+        });
+
+        Worker { name, stop, handle }
+    }
+
+    /// Stop the worker and return its result, or its first failure.
+    pub fn stop(self) -> Result<T> {
+        let _ = self.stop.send(());
+        self.handle
+            .join()
+            .unwrap_or_else(|_| panic!("{} panicked", self.name))
+    }
+}
+
+/// Tells the work of a `Worker` that it was stopped.
+pub(crate) struct StopSignal {
+    stopped: Receiver<()>,
+    start: Instant,
+}
+
+impl StopSignal {
+    /// Wait for `timeout`. False if the worker was stopped first.
+    pub fn sleep(&self, timeout: Duration) -> bool {
+        matches!(
+            self.stopped.recv_timeout(timeout),
+            Err(RecvTimeoutError::Timeout)
+        )
+    }
+
+    /// Wait until `at` after the worker started. False if the worker was
+    /// stopped first.
+    pub fn sleep_until(&self, at: Duration) -> bool {
+        self.sleep(at.saturating_sub(self.start.elapsed()))
+    }
+
+    /// Wait until the worker is stopped.
+    pub fn wait(&self) {
+        let _ = self.stopped.recv();
+    }
+}
@@ -1,13 +1,22 @@
-use crate::{ExecutionPlan, Stats, stats::AggregateStats};
+use crate::{ExecutionPlan, Stats, network::sysctl::SysctlSnapshot, stats::AggregateStats};
 use std::{
     fs::{File, create_dir_all},
     io::Write,
 };
+use uuid::Uuid;
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub(crate) struct Report {
     pub plan: ExecutionPlan,
+    /// Fewer than `plan.run_count` when boar was interrupted.
+    completed_runs: u16,
+    /// (hop, runs) for every hop which was the bottleneck of a run, see
+    /// `QdiscRun::bottleneck`.
+    bottlenecks: Vec<(String, u16)>,
+    /// Sysctls of the host and of every namespace once the network was
+    /// built.
+    sysctls: SysctlSnapshot,
     stat_report: Vec<StatsReport>,
 }
 
@@ -21,10 +30,19 @@ struct StatsReport {
 impl Report {
     // TODO make Vec<Stats>
     // pub fn new(plan: &ExecutionPlan, mut stats: Vec<Stats>) -> Self {
-    pub fn new(plan: &ExecutionPlan, stats: Vec<Stats>) -> Self {
+    pub fn new(
+        plan: &ExecutionPlan,
+        completed_runs: u16,
+        bottlenecks: Vec<(String, u16)>,
+        sysctls: SysctlSnapshot,
+        stats: Vec<Stats>,
+    ) -> Self {
         let dir = Self::create_report_dir(plan);
         let mut report = Report {
             plan: plan.clone(),
+            completed_runs,
+            bottlenecks,
+            sysctls,
             stat_report: vec![],
         };
 
@@ -49,8 +67,8 @@ impl Report {
         report
     }
 
-    fn create_report_dir(plan: &ExecutionPlan) -> String {
-        let dir = format!("report/{}", plan.uuid);
+    pub(crate) fn create_report_dir(plan: &ExecutionPlan) -> String {
+        let dir = dir(&plan.uuid);
         // make "/report"
         // make folder for this report based on uuid
         create_dir_all(&dir).unwrap();
@@ -59,3 +77,8 @@ impl Report {
         dir
     }
 }
+
+/// Directory of the report of the plan `uuid`.
+pub(crate) fn dir(uuid: &Uuid) -> String {
+    format!("report/{uuid}")
+}
@@ -2,7 +2,7 @@ use crate::stats::ToStatMetric;
 use regex::Regex;
 use std::fmt::Debug;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct DeliveryRate {
     rate: usize,
 }
@@ -0,0 +1,44 @@
+use crate::stats::ToStatMetric;
+use regex::Regex;
+use std::fmt::Debug;
+
+/// Whether a download finished, e.g. rather than idling out after a path
+/// change. Its mean is the share of completed downloads.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadCompletion {
+    pub(crate) completed: bool,
+}
+
+impl DownloadCompletion {
+    pub fn new_from_logs(logs: &str) -> Self {
+        // "1/1 response(s) received in 18.767083ms"
+        let re = Regex::new(r"response\(s\) received in ").unwrap();
+
+        DownloadCompletion {
+            completed: re.is_match(logs),
+        }
+    }
+}
+
+impl ToStatMetric for DownloadCompletion {
+    fn as_f64(&self) -> f64 {
+        if self.completed { 1.0 } else { 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn download_completion() {
+        let logs = "[2025-12-15T04:12:15.914151000Z INFO  quiche_apps::common] 1/1 response(s) received in 18.767083ms, closing...";
+        assert!(DownloadCompletion::new_from_logs(logs).completed);
+
+        let logs = "[2025-12-15T04:12:15.895071000Z INFO  quiche_apps::client] connecting to 10.55.10.1:9999 from 0.0.0.0:52522 with scid eff94d1df3d374a001a807c4c5b7b44fca82e6aa \
+         [2025-12-15T04:12:20.914211000Z INFO  quiche_apps::client] connection timed out after 5.019140125s";
+        let metric = DownloadCompletion::new_from_logs(logs);
+        assert!(!metric.completed);
+        assert_eq!(metric.as_f64(), 0.0);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::stats::ToStatMetric;
+use byte_unit::Byte;
 use regex::Regex;
 use std::{fmt::Debug, time::Duration};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct DownloadDuration {
     pub(crate) duration: Duration,
 }
@@ -45,6 +46,13 @@ impl DownloadDuration {
             duration: Duration::from_millis(download_duration as u64),
         }
     }
+
+    /// Goodput of a download of `download_bytes` (bits/s).
+    pub fn goodput(&self, download_bytes: &Byte) -> f64 {
+        // durations are parsed with ms resolution
+        let secs = self.duration.max(Duration::from_millis(1)).as_secs_f64();
+        download_bytes.as_u64() as f64 * 8.0 / secs
+    }
 }
 
 impl ToStatMetric for DownloadDuration {
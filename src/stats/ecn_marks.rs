@@ -0,0 +1,93 @@
+use crate::stats::ToStatMetric;
+use regex::Regex;
+use std::fmt::Debug;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcnMarks {
+    marks: u64,
+    drops: u64,
+}
+
+impl EcnMarks {
+    /// Sum the ECN marks and drops of every qdisc in `tc -s qdisc show`
+    /// output.
+    pub fn new_from_qdisc_stats(stats: &str) -> Self {
+        // "Sent 1234 bytes 5 pkt (dropped 0, overlimits 0 requeues 0)"
+        let drops = Regex::new(r"\(dropped ([0-9]+),").unwrap();
+        // "ecn_mark 0" for fq_codel, pie and dualpi2, "marked 0" for red
+        let marks = Regex::new(r"\b(?:ecn_mark|marked) ([0-9]+)").unwrap();
+
+        let sum = |re: &Regex| {
+            re.captures_iter(stats)
+                .map(|caps| caps[1].parse::<u64>().unwrap())
+                .sum()
+        };
+
+        EcnMarks {
+            marks: sum(&marks),
+            drops: sum(&drops),
+        }
+    }
+
+    /// Marks and drops since an earlier snapshot of the same qdiscs.
+    pub fn since(&self, before: &Self) -> Self {
+        EcnMarks {
+            marks: self.marks.saturating_sub(before.marks),
+            drops: self.drops.saturating_sub(before.drops),
+        }
+    }
+}
+
+impl ToStatMetric for EcnMarks {
+    // Share of the congestion signals which were marks rather than drops, 0
+    // when the bottleneck sent none.
+    fn as_f64(&self) -> f64 {
+        let signals = self.marks + self.drops;
+        if signals == 0 {
+            return 0.0;
+        }
+        self.marks as f64 / signals as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ecn_marks_fq_codel() {
+        let before = "qdisc fq_codel 99: parent 1:99 limit 10800p flows 1024 quantum 1514 target 5ms interval 100ms memory_limit 32Mb ecn drop_batch 64 \n \
+             Sent 1038727 bytes 794 pkt (dropped 2, overlimits 0 requeues 0) \n \
+             backlog 0b 0p requeues 0\n  \
+              maxpacket 1392 drop_overlimit 0 new_flow_count 1 ecn_mark 6\n  \
+              new_flows_len 0 old_flows_len 0\n";
+        let after = "qdisc fq_codel 99: parent 1:99 limit 10800p flows 1024 quantum 1514 target 5ms interval 100ms memory_limit 32Mb ecn drop_batch 64 \n \
+             Sent 2077454 bytes 1588 pkt (dropped 3, overlimits 0 requeues 0) \n \
+             backlog 0b 0p requeues 0\n  \
+              maxpacket 1392 drop_overlimit 0 new_flow_count 2 ecn_mark 9\n  \
+              new_flows_len 0 old_flows_len 0\n";
+
+        let metric =
+            EcnMarks::new_from_qdisc_stats(after).since(&EcnMarks::new_from_qdisc_stats(before));
+        assert_eq!(metric, EcnMarks { marks: 3, drops: 1 });
+        assert_eq!(metric.as_f64(), 0.75);
+    }
+
+    #[test]
+    fn ecn_marks_red_and_pie() {
+        // two bottlenecks, the pie xstats "dropped" is not counted twice
+        let stats = "qdisc red 99: parent 1:99 limit 250000b min 20833b max 62500b ecn \n \
+             Sent 1038727 bytes 794 pkt (dropped 4, overlimits 10 requeues 0) \n \
+             backlog 0b 0p requeues 0\n  \
+              marked 6 early 4 pdrop 0 other 0\n\
+             qdisc pie 99: parent 1:99 limit 1000p target 15ms tupdate 15ms alpha 2 beta 20 ecn \n \
+             Sent 15318 bytes 291 pkt (dropped 1, overlimits 0 requeues 0) \n \
+             backlog 0b 0p requeues 0\n  \
+              prob 0 delay 0us\n  \
+              pkts_in 291 overlimit 0 dropped 1 maxq 0 ecn_mark 0\n";
+
+        let metric = EcnMarks::new_from_qdisc_stats(stats);
+        assert_eq!(metric, EcnMarks { marks: 6, drops: 5 });
+        assert_eq!(EcnMarks::default().as_f64(), 0.0);
+    }
+}
@@ -0,0 +1,99 @@
+use std::{ops::Range, time::Duration};
+
+/// A sample interval in which a client received less than a full packet
+/// does not count as its flow being active.
+const ACTIVE_BYTES: u64 = 1500;
+
+/// The rate of every flow (bits/s) over the stretch of the run in which all
+/// of them were active. `delivered` holds the bytes received by the client
+/// namespace of every flow, sampled at the same times, so every flow needs a
+/// client namespace of its own. A flow is active from the first to the last
+/// sample interval in which it received at least `ACTIVE_BYTES`.
+///
+/// None when the flows were never all active at once.
+pub fn while_all_active(delivered: &[&[(Duration, u64)]]) -> Option<Vec<f64>> {
+    let windows = delivered
+        .iter()
+        .map(|samples| active(samples))
+        .collect::<Option<Vec<_>>>()?;
+    let from = windows.iter().map(|window| window.start).max()?;
+    let to = windows.iter().map(|window| window.end).min()?;
+    if to <= from {
+        return None;
+    }
+
+    let secs = (to - from).as_secs_f64();
+    Some(
+        delivered
+            .iter()
+            .map(|samples| bytes_at(samples, to).saturating_sub(bytes_at(samples, from)))
+            .map(|bytes| bytes as f64 * 8.0 / secs)
+            .collect(),
+    )
+}
+
+/// From the start of the first to the end of the last active interval.
+fn active(samples: &[(Duration, u64)]) -> Option<Range<Duration>> {
+    let mut active = samples
+        .windows(2)
+        .filter(|samples| samples[1].1.saturating_sub(samples[0].1) >= ACTIVE_BYTES);
+    let first = active.next()?;
+    let last = active.next_back().unwrap_or(first);
+    Some(first[0].0..last[1].0)
+}
+
+/// The bytes of the last sample taken at or before `at`.
+fn bytes_at(samples: &[(Duration, u64)], at: Duration) -> u64 {
+    samples
+        .iter()
+        .rev()
+        .find(|(sampled, _)| *sampled <= at)
+        .map_or(0, |(_, bytes)| *bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples every 100 ms over 2 s of a flow which received `bytes(at)` in
+    /// the interval ending at `at` ms.
+    fn flow(bytes: impl Fn(u64) -> u64) -> Vec<(Duration, u64)> {
+        let mut received = 0;
+        (0..=20)
+            .map(|i| {
+                let at = i * 100;
+                if at > 0 {
+                    received += bytes(at);
+                }
+                (Duration::from_millis(at), received)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flow_rate_while_all_active() {
+        // the first flow has the link to itself until the second joins at
+        // 800 ms, they share it evenly until the first completes at 1600 ms,
+        // and the second finishes alone
+        let first = flow(|at| match at {
+            ..=800 => 20_000,
+            801..=1600 => 10_000,
+            _ => 0,
+        });
+        let second = flow(|at| match at {
+            ..=800 => 0,
+            801..=1600 => 10_000,
+            _ => 20_000,
+        });
+        assert_eq!(
+            while_all_active(&[&first, &second]),
+            Some(vec![800_000.0, 800_000.0])
+        );
+
+        // flows which never overlap
+        let late = flow(|at| if at > 1600 { 20_000 } else { 0 });
+        assert_eq!(while_all_active(&[&first, &late]), None);
+        // and a flow which never received anything
+        assert_eq!(while_all_active(&[&first, &flow(|_| 0)]), None);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::stats::ToStatMetric;
+use std::fmt::Debug;
+
+/// Jain's fairness index over the goodput of the concurrent flows of a run:
+/// 1 when every flow gets the same goodput, 1/n when one flow gets it all.
+#[derive(Default, Debug)]
+pub struct JainFairness {
+    index: f64,
+}
+
+impl JainFairness {
+    pub fn new_from_goodputs(goodputs: &[f64]) -> Self {
+        let sum: f64 = goodputs.iter().sum();
+        let sum_squares: f64 = goodputs.iter().map(|goodput| goodput * goodput).sum();
+
+        let index = if sum_squares == 0.0 {
+            0.0
+        } else {
+            sum * sum / (goodputs.len() as f64 * sum_squares)
+        };
+
+        JainFairness { index }
+    }
+}
+
+impl ToStatMetric for JainFairness {
+    fn as_f64(&self) -> f64 {
+        self.index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jain_fairness() {
+        let metric = JainFairness::new_from_goodputs(&[10.0, 10.0, 10.0]);
+        assert_eq!(metric.index, 1.0);
+
+        let metric = JainFairness::new_from_goodputs(&[30.0, 0.0, 0.0]);
+        assert_eq!(metric.index, 1.0 / 3.0);
+
+        let metric = JainFairness::new_from_goodputs(&[15.0, 5.0]);
+        assert_eq!(metric.index, 0.8);
+    }
+}
@@ -7,8 +7,17 @@ use statrs::statistics::{Data, Distribution, OrderStatistics};
 use std::{any::type_name, fmt::Debug};
 
 pub mod delivery_rate;
+pub mod download_completion;
 pub mod download_duration;
+pub mod ecn_marks;
+pub mod flow_rate;
+pub mod jain_fairness;
+pub mod path_change_stall;
+pub mod pmtu;
+pub mod qdisc;
+pub mod recovery_time;
 pub mod startup_exit;
+pub mod throughput_share;
 
 // A metric over which we can calculate statistics.
 pub trait ToStatMetric: Debug {
@@ -18,6 +27,8 @@ pub trait ToStatMetric: Debug {
 #[derive(Debug)]
 pub struct Stats {
     name: String,
+    /// Set for the results of a single server, see `ServerSetup::tag`.
+    tag: Option<String>,
     #[allow(dead_code)]
     raw_metrics: Vec<Box<dyn ToStatMetric>>,
     stat_data: Data<Vec<f64>>,
@@ -38,24 +49,39 @@ impl Stats {
 
         Stats {
             name,
+            tag: None,
             raw_metrics,
             stat_data: data,
         }
     }
 
+    /// The same stats, named after `tag` too.
+    pub fn tagged(mut self, tag: String) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
     pub fn name(&self) -> String {
-        ccase!(snake, &self.name)
+        let name = ccase!(snake, &self.name);
+        match &self.tag {
+            Some(tag) => format!("{name}_{tag}"),
+            None => name,
+        }
     }
 
     pub fn aggregate(&mut self) -> AggregateStats {
-        AggregateStats::new(self.name.clone(), &mut self.stat_data)
+        let name = match &self.tag {
+            Some(tag) => format!("{} {tag}", self.name),
+            None => self.name.clone(),
+        };
+        AggregateStats::new(name, &mut self.stat_data)
     }
 
     pub(crate) fn plot_cdf(&self, dir: &str) -> String {
         let cdf_data = self.cdf();
 
         let mut plot = plotly::Plot::new();
-        let (x, y): (Vec<_>, Vec<_>) = cdf_data.into_iter().map(|(a, b)| (a, b)).unzip();
+        let (x, y): (Vec<_>, Vec<_>) = cdf_data.into_iter().unzip();
 
         // Graph
         let trace = Scatter::new(x, y)
@@ -65,7 +91,7 @@ impl Stats {
             .y_axis("y");
         plot.add_trace(trace);
 
-        let title = format!("{}", "title");
+        let title = "title";
         let layout = Layout::new()
             .title(format!("{} Cumulative distribution function", title))
             .show_legend(true)
@@ -92,7 +118,7 @@ impl Stats {
 
         x_ord.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
-        if let Some(mut previous) = x_ord.get(0).map(|&f| f) {
+        if let Some(mut previous) = x_ord.first().copied() {
             let mut cdf = Vec::new();
             for (i, f) in x_ord.into_iter().enumerate() {
                 if f != previous {
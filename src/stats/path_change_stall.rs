@@ -0,0 +1,106 @@
+use crate::stats::ToStatMetric;
+use std::{fmt::Debug, time::Duration};
+
+/// A sample interval in which the client received less than a full packet
+/// counts as stalled.
+const STALL_BYTES: u64 = 1500;
+
+/// How long the client stopped receiving after a path change.
+#[derive(Default, Debug)]
+pub struct PathChangeStall {
+    /// Index into the path changes of the plan, recorded in the data file.
+    #[allow(dead_code)]
+    change: usize,
+    stall: Duration,
+}
+
+impl PathChangeStall {
+    /// `delivered` holds the bytes received by the client since the start of
+    /// the run, sampled during the run.
+    ///
+    /// The stall is the first stretch of stalled samples from the change on,
+    /// until delivery resumes or, if the download did not `complete`, the
+    /// run ends. It is zero when delivery never stalled.
+    pub fn new_from_delivered(
+        delivered: &[(Duration, u64)],
+        change: usize,
+        at: Duration,
+        completed: bool,
+    ) -> Self {
+        // the interval the change happened in counts too
+        let from = delivered
+            .iter()
+            .rposition(|(sampled, _)| *sampled <= at)
+            .unwrap_or(0);
+
+        let mut stalled: Option<(Duration, Duration)> = None;
+        let mut resumed = false;
+        for samples in delivered[from..].windows(2) {
+            let [(start, before), (end, after)] = samples else {
+                unreachable!("windows of 2");
+            };
+            if after.saturating_sub(*before) < STALL_BYTES {
+                let since = stalled.map_or(*start, |(since, _)| since);
+                stalled = Some((since, *end));
+            } else if stalled.is_some() {
+                resumed = true;
+                break;
+            }
+        }
+        if completed && !resumed {
+            // idle after the download finished
+            stalled = None;
+        }
+
+        PathChangeStall {
+            change,
+            stall: stalled.map_or(Duration::ZERO, |(since, until)| until - since),
+        }
+    }
+}
+
+impl ToStatMetric for PathChangeStall {
+    fn as_f64(&self) -> f64 {
+        self.stall.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn path_change_stall() {
+        let delivered = [
+            (ms(0), 0),
+            (ms(100), 10_000),
+            (ms(200), 20_000),
+            // rebind at 250ms, nothing arrives until 500ms
+            (ms(300), 20_500),
+            (ms(400), 20_500),
+            (ms(500), 20_600),
+            (ms(600), 30_000),
+            // the download completed
+            (ms(700), 30_000),
+        ];
+
+        let metric = PathChangeStall::new_from_delivered(&delivered, 0, ms(250), true);
+        assert_eq!(metric.stall, ms(300));
+        assert!((metric.as_f64() - 0.3).abs() < 1e-9);
+
+        let metric = PathChangeStall::new_from_delivered(&delivered[..3], 1, ms(50), true);
+        assert_eq!((metric.change, metric.stall), (1, Duration::ZERO));
+
+        // never resumed
+        let metric = PathChangeStall::new_from_delivered(&delivered[..5], 0, ms(250), false);
+        assert_eq!(metric.stall, ms(200));
+
+        // after the download completed
+        let metric = PathChangeStall::new_from_delivered(&delivered, 0, ms(650), true);
+        assert_eq!(metric.stall, Duration::ZERO);
+    }
+}
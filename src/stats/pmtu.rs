@@ -0,0 +1,88 @@
+use crate::stats::ToStatMetric;
+use regex::Regex;
+use std::{fmt::Debug, sync::LazyLock};
+
+/// "pmtu=1350" in the stats of a path.
+static PMTU: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bpmtu=([0-9]+)").unwrap());
+
+/// Path MTU the client ended its connection with, in bytes.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ClientPmtu {
+    pmtu: u64,
+}
+
+impl ClientPmtu {
+    /// The `pmtu=` of the client's active path, if it printed its stats.
+    pub fn new_from_logs(logs: &str) -> Option<Self> {
+        last_pmtu(logs).map(|pmtu| ClientPmtu { pmtu })
+    }
+}
+
+impl ToStatMetric for ClientPmtu {
+    fn as_f64(&self) -> f64 {
+        self.pmtu as f64
+    }
+}
+
+/// Path MTU of a server connection when its stream closed, in bytes.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ServerPmtu {
+    pmtu: u64,
+}
+
+impl ServerPmtu {
+    /// One metric for every line of `logs` with the stats of an active path.
+    pub fn new_from_logs(logs: &[String]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|log| last_pmtu(log))
+            .map(|pmtu| ServerPmtu { pmtu })
+            .collect()
+    }
+}
+
+impl ToStatMetric for ServerPmtu {
+    fn as_f64(&self) -> f64 {
+        self.pmtu as f64
+    }
+}
+
+/// Parse the "pmtu=1350" of the last active path in `logs`. The stats of
+/// every path start with "local_addr=", and those of a path the connection
+/// only probed hold "active=false".
+fn last_pmtu(logs: &str) -> Option<u64> {
+    logs.split("local_addr=")
+        .filter(|path| path.contains("active=true"))
+        .filter_map(|path| PMTU.captures(path))
+        .last()
+        .map(|caps| caps[1].parse().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_pmtu() {
+        let logs = "[2025-12-15T04:12:15.895071000Z INFO  quiche_apps::client] connecting to 127.0.0.1:9999 from 0.0.0.0:52522 with scid eff94d1df3d374a001a807c4c5b7b44fca82e6aa \
+         [2025-12-15T04:12:15.914151000Z INFO  quiche_apps::common] 1/1 response(s) received in 18.767083ms, closing... \
+         [2025-12-15T04:12:15.914211000Z INFO  quiche_apps::client] connection closed, recv=794 sent=291 lost=0 retrans=0 sent_bytes=15318 recv_bytes=1038727 lost_bytes=0 [local_addr=0.0.0.0:52522 peer_addr=127.0.0.1:9999 validation_state=Validated active=true recv=794 sent=291 lost=0 retrans=0 rtt=923.083µs min_rtt=Some(144.738µs) rttvar=937.037µs cwnd=13500 sent_bytes=15318 recv_bytes=1038727 lost_bytes=0 stream_retrans_bytes=0 pmtu=1350 delivery_rate=1997003] \
+         [local_addr=0.0.0.0:52523 peer_addr=127.0.0.1:9999 validation_state=Validated active=false recv=0 sent=2 lost=0 retrans=0 rtt=1ms min_rtt=None rttvar=1ms cwnd=13500 sent_bytes=96 recv_bytes=0 lost_bytes=0 stream_retrans_bytes=0 pmtu=1200 delivery_rate=0]";
+
+        let metric = ClientPmtu::new_from_logs(logs).unwrap();
+        assert_eq!(metric.pmtu, 1350);
+        assert!(ClientPmtu::new_from_logs("connection timed out").is_none());
+    }
+
+    #[test]
+    fn server_pmtu() {
+        let logs: Vec<String> = [
+            "[2025-12-20T07:09:59.547130000Z INFO  async_http3_server] received new connection!",
+            "[2025-12-20T07:09:59.565448000Z INFO  async_http3_server::server] stream closed: Some(local_addr=0.0.0.0:9999 peer_addr=127.0.0.1:61757 validation_state=Validated active=true recv=274 sent=794 lost=0 retrans=0 rtt=251.283µs min_rtt=Some(100.291µs) rttvar=165.768µs cwnd=101824 sent_bytes=1038667 recv_bytes=14030 lost_bytes=0 stream_retrans_bytes=0 pmtu=1350 delivery_rate=110391822 max_bandwidth=Some(110391822) startup_exit=None )",
+            "[2025-12-20T07:10:01.565448000Z INFO  async_http3_server::server] stream closed: Some(local_addr=0.0.0.0:9999 peer_addr=127.0.0.1:61758 validation_state=Validated active=true recv=274 sent=794 lost=0 retrans=0 rtt=251.283µs min_rtt=Some(100.291µs) rttvar=165.768µs cwnd=101824 sent_bytes=1038667 recv_bytes=14030 lost_bytes=0 stream_retrans_bytes=0 pmtu=1232 delivery_rate=110391822 max_bandwidth=Some(110391822) startup_exit=None )",
+        ].into_iter().map(|v| v.to_string()).collect();
+
+        let metrics = ServerPmtu::new_from_logs(&logs);
+        assert_eq!(metrics.len(), 2);
+        assert_eq!((metrics[0].pmtu, metrics[1].pmtu), (1350, 1232));
+    }
+}
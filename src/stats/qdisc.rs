@@ -0,0 +1,411 @@
+use crate::stats::ToStatMetric;
+use regex::Regex;
+use std::{fmt::Debug, time::Duration};
+
+/// Counters of the root qdisc of a hop, which include those of its children.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QdiscCounters {
+    pub drops: u64,
+    pub overlimits: u64,
+    pub requeues: u64,
+    pub backlog_bytes: u64,
+}
+
+impl QdiscCounters {
+    /// Parse the first qdisc of `tc -s qdisc show dev <dev> root` output.
+    pub fn new_from_qdisc_stats(stats: &str) -> Self {
+        // "Sent 1234 bytes 5 pkt (dropped 0, overlimits 0 requeues 0)"
+        let sent =
+            Regex::new(r"\(dropped ([0-9]+), overlimits ([0-9]+) requeues ([0-9]+)\)").unwrap();
+        // "backlog 15140b 10p", tc rounds sizes close to a multiple to Kb or Mb
+        let backlog = Regex::new(r"backlog ([0-9.]+)(b|Kb|Mb) ").unwrap();
+
+        let mut counters = QdiscCounters::default();
+        if let Some(caps) = sent.captures(stats) {
+            counters.drops = caps[1].parse().unwrap();
+            counters.overlimits = caps[2].parse().unwrap();
+            counters.requeues = caps[3].parse().unwrap();
+        }
+        if let Some(caps) = backlog.captures(stats) {
+            let size: f64 = caps[1].parse().unwrap();
+            let unit = match &caps[2] {
+                "Kb" => 1024.0,
+                "Mb" => 1024.0 * 1024.0,
+                _ => 1.0,
+            };
+            counters.backlog_bytes = (size * unit) as u64;
+        }
+
+        counters
+    }
+}
+
+/// A counter of every hop, named `<namespace>/<dev>`.
+#[derive(Default, Debug, Clone)]
+pub struct HopCounts(pub Vec<(String, u64)>);
+
+impl HopCounts {
+    fn sum(&self) -> u64 {
+        self.0.iter().map(|(_, count)| count).sum()
+    }
+
+    fn max(&self) -> u64 {
+        self.0.iter().map(|(_, count)| *count).max().unwrap_or(0)
+    }
+}
+
+/// Packets dropped at every hop during a run.
+#[derive(Default, Debug)]
+pub struct QdiscDrops(HopCounts);
+
+/// Times a shaper held back a packet because it was over its rate.
+#[derive(Default, Debug)]
+pub struct QdiscOverlimits(HopCounts);
+
+#[derive(Default, Debug)]
+pub struct QdiscRequeues(HopCounts);
+
+/// Largest queue seen at every hop while sampling a run (bytes).
+#[derive(Default, Debug)]
+pub struct QdiscMaxBacklog(HopCounts);
+
+/// A stretch of delivery below this share of the rate the client received
+/// at its best counts as a dip.
+const DIP_SHARE: f64 = 0.5;
+
+/// Share of the delivery samples, once the client reached its rate, which
+/// must be dips for the loss to look like policing.
+const DIPS_SHARE: f64 = 0.1;
+
+/// Fewer delivery samples at the rate tell nothing about its loss pattern.
+const MIN_SAMPLES: usize = 5;
+
+/// The bottleneck of a run when it policed, told from how delivery to the
+/// client behaves around the loss. A shaper drops from a queue which keeps
+/// draining at its rate, so delivery stays flat through a loss. A policer has
+/// no queue to drain: delivery reaches the token rate, and every loss burst
+/// leaves it dipping well below until the sender ramps up again.
+#[derive(Default, Debug)]
+pub struct Policing {
+    hop: Option<String>,
+}
+
+impl Policing {
+    /// The policing hop, if any.
+    pub fn hop(&self) -> Option<&str> {
+        self.hop.as_deref()
+    }
+}
+
+/// The qdisc metrics of one run.
+#[derive(Default, Debug)]
+pub struct QdiscRun {
+    pub drops: QdiscDrops,
+    pub overlimits: QdiscOverlimits,
+    pub requeues: QdiscRequeues,
+    pub max_backlog: QdiscMaxBacklog,
+    /// Bytes received by every client namespace since the start of the run,
+    /// sampled with the backlog.
+    delivered: Vec<(String, Vec<(Duration, u64)>)>,
+}
+
+impl QdiscRun {
+    /// `hops` holds the counters of every hop at the start and end of the
+    /// run, and the largest backlog sampled in between.
+    pub fn new(
+        hops: &[(String, QdiscCounters, QdiscCounters, u64)],
+        delivered: Vec<(String, Vec<(Duration, u64)>)>,
+    ) -> Self {
+        let delta = |counter: fn(&QdiscCounters) -> u64| {
+            HopCounts(
+                hops.iter()
+                    .map(|(hop, before, after, _)| {
+                        (hop.clone(), counter(after).saturating_sub(counter(before)))
+                    })
+                    .collect(),
+            )
+        };
+
+        QdiscRun {
+            drops: QdiscDrops(delta(|counters| counters.drops)),
+            overlimits: QdiscOverlimits(delta(|counters| counters.overlimits)),
+            requeues: QdiscRequeues(delta(|counters| counters.requeues)),
+            max_backlog: QdiscMaxBacklog(HopCounts(
+                hops.iter()
+                    .map(|(hop, _, _, max_backlog)| (hop.clone(), *max_backlog))
+                    .collect(),
+            )),
+            delivered,
+        }
+    }
+}
+
+impl QdiscRun {
+    /// Bytes received by the `client` namespace since the start of the run,
+    /// empty when it was not sampled.
+    pub fn delivered(&self, client: &str) -> &[(Duration, u64)] {
+        self.delivered
+            .iter()
+            .find(|(ns, _)| ns == client)
+            .map_or(&[], |(_, samples)| samples)
+    }
+
+    /// The hop which limited the run: of the hops which held back packets
+    /// over their rate, the one with the most drops, or without drops the
+    /// one with the largest backlog. netem drops and holds packets without
+    /// ever being over a limit, so its loss and delay do not count.
+    pub fn bottleneck(&self) -> Option<&str> {
+        let hops = &self.overlimits.0.0;
+        hops.iter()
+            .enumerate()
+            .filter(|(_, (_, overlimits))| *overlimits > 0)
+            .max_by_key(|(i, _)| (self.drops.0.0[*i].1, self.max_backlog.0.0[*i].1))
+            .map(|(_, (hop, _))| hop.as_str())
+    }
+
+    /// Whether the loss at the bottleneck looks like policing, from the
+    /// bytes received by the `client` namespace.
+    pub fn policing(&self, client: &str) -> Policing {
+        let hops = &self.drops.0.0;
+        let lossy = self.bottleneck().filter(|bottleneck| {
+            hops.iter()
+                .any(|(hop, drops)| hop == bottleneck && *drops > 0)
+        });
+        let hop = lossy.filter(|_| delivery_dips(self.delivered(client)));
+
+        Policing {
+            hop: hop.map(str::to_string),
+        }
+    }
+}
+
+/// Whether delivery kept dipping below `DIP_SHARE` of its best rate between
+/// the first and the last sample at that rate.
+fn delivery_dips(delivered: &[(Duration, u64)]) -> bool {
+    let rates: Vec<f64> = delivered
+        .windows(2)
+        .map(|samples| {
+            let [(start, before), (end, after)] = samples else {
+                unreachable!("windows of 2");
+            };
+            let secs = (*end - *start).as_secs_f64();
+            if secs == 0.0 {
+                return 0.0;
+            }
+            after.saturating_sub(*before) as f64 / secs
+        })
+        .collect();
+
+    // the 90th percentile, as a single sample may catch a burst
+    let mut sorted = rates.clone();
+    sorted.sort_by(f64::total_cmp);
+    let Some(&best) = sorted.get(sorted.len().saturating_sub(1) * 9 / 10) else {
+        return false;
+    };
+    // from when the client first reached its rate until the download ended
+    let at_rate = |rate: &f64| *rate >= best * (1.0 - DIP_SHARE / 2.0);
+    let (Some(first), Some(last)) = (
+        rates.iter().position(at_rate),
+        rates.iter().rposition(at_rate),
+    ) else {
+        return false;
+    };
+    let rates = &rates[first..=last];
+    if rates.len() < MIN_SAMPLES {
+        return false;
+    }
+
+    let dips = rates
+        .iter()
+        .filter(|rate| **rate < best * DIP_SHARE)
+        .count();
+    dips as f64 >= rates.len() as f64 * DIPS_SHARE
+}
+
+impl ToStatMetric for Policing {
+    fn as_f64(&self) -> f64 {
+        if self.hop.is_some() { 1.0 } else { 0.0 }
+    }
+}
+
+impl ToStatMetric for QdiscDrops {
+    fn as_f64(&self) -> f64 {
+        self.0.sum() as f64
+    }
+}
+
+impl ToStatMetric for QdiscOverlimits {
+    fn as_f64(&self) -> f64 {
+        self.0.sum() as f64
+    }
+}
+
+impl ToStatMetric for QdiscRequeues {
+    fn as_f64(&self) -> f64 {
+        self.0.sum() as f64
+    }
+}
+
+impl ToStatMetric for QdiscMaxBacklog {
+    fn as_f64(&self) -> f64 {
+        self.0.max() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qdisc_counters() {
+        let stats = "qdisc htb 1: root refcnt 2 r2q 10 default 0x99 direct_packets_stat 0 direct_qlen 1000\n \
+             Sent 1038727 bytes 794 pkt (dropped 12, overlimits 340 requeues 1) \n \
+             backlog 15140b 10p requeues 1\n\
+             qdisc pfifo 99: parent 1:99 limit 10800p\n \
+             Sent 1038727 bytes 794 pkt (dropped 12, overlimits 0 requeues 0) \n \
+             backlog 15140b 10p requeues 0\n";
+
+        assert_eq!(
+            QdiscCounters::new_from_qdisc_stats(stats),
+            QdiscCounters {
+                drops: 12,
+                overlimits: 340,
+                requeues: 1,
+                backlog_bytes: 15140,
+            }
+        );
+
+        let stats = "qdisc netem 1: root refcnt 2 limit 1000000 delay 50ms\n \
+             Sent 0 bytes 0 pkt (dropped 0, overlimits 0 requeues 0) \n \
+             backlog 1.5Mb 1048p requeues 0\n";
+        assert_eq!(
+            QdiscCounters::new_from_qdisc_stats(stats).backlog_bytes,
+            1_572_864
+        );
+
+        // `tc -s filter show` of a policer
+        let stats = "filter parent ffff:fff3 protocol all pref 49152 u32 chain 0 \n\
+             filter parent ffff:fff3 protocol all pref 49152 u32 chain 0 fh 800::800 order 2048 key ht 800 bkt 0 terminal flowid not_in_hw  (rule hit 1200 success 1200)\n  \
+             match 00000000/00000000 at 0 (success 1200 ) \n\
+             \taction order 1:  police 0x1 rate 5Mbit burst 64000b mtu 2Kb action drop/pass overhead 0 \n\
+             \tref 1 bind 1  installed 12 sec used 0 sec firstused 11 sec\n\
+             \tAction statistics:\n\
+             \tSent 1563000 bytes 1150 pkt (dropped 50, overlimits 50 requeues 0) \n\
+             \tbacklog 0b 0p requeues 0\n";
+        assert_eq!(
+            QdiscCounters::new_from_qdisc_stats(stats),
+            QdiscCounters {
+                drops: 50,
+                overlimits: 50,
+                requeues: 0,
+                backlog_bytes: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn qdisc_run() {
+        let before = QdiscCounters {
+            drops: 2,
+            overlimits: 10,
+            requeues: 0,
+            backlog_bytes: 0,
+        };
+        let after = QdiscCounters {
+            drops: 5,
+            overlimits: 30,
+            requeues: 1,
+            backlog_bytes: 0,
+        };
+        let hops = [
+            ("ns_m1/veth_m1_m2".to_string(), before, after, 30_000),
+            ("ns_m2/veth_m2_m3".to_string(), before, before, 1_500),
+        ];
+
+        let run = QdiscRun::new(&hops, Vec::new());
+        assert_eq!(run.drops.as_f64(), 3.0);
+        assert_eq!(run.overlimits.as_f64(), 20.0);
+        assert_eq!(run.requeues.as_f64(), 1.0);
+        assert_eq!(run.max_backlog.as_f64(), 30_000.0);
+        assert_eq!(run.drops.0.0[1], ("ns_m2/veth_m2_m3".to_string(), 0));
+        assert_eq!(run.bottleneck(), Some("ns_m1/veth_m1_m2"));
+    }
+
+    #[test]
+    fn qdisc_run_bottleneck() {
+        let counters = |drops, overlimits| QdiscCounters {
+            drops,
+            overlimits,
+            ..Default::default()
+        };
+        let zero = counters(0, 0);
+        let hops = |m2_drops, m3_drops| {
+            [
+                // lossy netem, never over a limit
+                ("ns_m1/netem".to_string(), zero, counters(50, 0), 90_000),
+                (
+                    "ns_m2/htb".to_string(),
+                    zero,
+                    counters(m2_drops, 100),
+                    20_000,
+                ),
+                (
+                    "ns_m3/htb".to_string(),
+                    zero,
+                    counters(m3_drops, 100),
+                    60_000,
+                ),
+            ]
+        };
+
+        let run = QdiscRun::new(&hops(5, 0), Vec::new());
+        assert_eq!(run.bottleneck(), Some("ns_m2/htb"));
+        let run = QdiscRun::new(&hops(0, 0), Vec::new());
+        assert_eq!(run.bottleneck(), Some("ns_m3/htb"));
+
+        let idle = [("ns_m2/htb".to_string(), zero, zero, 0)];
+        assert_eq!(QdiscRun::new(&idle, Vec::new()).bottleneck(), None);
+    }
+
+    #[test]
+    fn qdisc_run_policing() {
+        let counters = |drops| QdiscCounters {
+            drops,
+            overlimits: 100,
+            ..Default::default()
+        };
+        let zero = QdiscCounters::default();
+        // bytes received in every 100 ms after a ramp up, then nothing once
+        // the download completed
+        let delivered = |rates: &[u64]| {
+            let mut received = 0;
+            let rates = [10_000, 40_000, 80_000].iter().chain(rates).chain(&[0, 0]);
+            let mut samples = vec![(Duration::ZERO, 0)];
+            for (i, rate) in rates.enumerate() {
+                received += rate;
+                samples.push((Duration::from_millis(100 * (i as u64 + 1)), received));
+            }
+            vec![("ns_c1".to_string(), samples)]
+        };
+
+        // a policer and a shaper which lost as much
+        let policed = [125_000, 125_000, 125_000, 40_000, 80_000].repeat(4);
+        let policer = [("ns_m2/police".to_string(), zero, counters(40), 0)];
+        let policing = QdiscRun::new(&policer, delivered(&policed)).policing("ns_c1");
+        assert_eq!(policing.hop(), Some("ns_m2/police"));
+        assert_eq!(policing.as_f64(), 1.0);
+
+        // the queue of a shaper keeps delivery at its rate through a loss
+        let shaped = [125_000; 20];
+        let shaper = [("ns_m2/htb".to_string(), zero, counters(40), 30_000)];
+        let run = QdiscRun::new(&shaper, delivered(&shaped));
+        assert_eq!(run.policing("ns_c1").hop(), None);
+        // and a policer below its rate drops nothing
+        let idle = [("ns_m2/police".to_string(), zero, counters(0), 0)];
+        let run = QdiscRun::new(&idle, delivered(&policed));
+        assert_eq!(run.policing("ns_c1").hop(), None);
+        // nor does a run without delivery samples tell anything
+        let run = QdiscRun::new(&policer, Vec::new());
+        assert_eq!(run.policing("ns_c1").hop(), None);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::stats::ToStatMetric;
+use std::{fmt::Debug, ops::Range, time::Duration};
+
+/// The goodput before an outage is averaged over this long.
+const PRE_OUTAGE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Share of the goodput before an outage which counts as recovered.
+const RECOVERED_SHARE: f64 = 0.8;
+
+/// Time from the end of an outage until the goodput towards the client is
+/// back to `RECOVERED_SHARE` of what it was before the outage.
+#[derive(Default, Debug)]
+pub struct RecoveryTime {
+    /// Index into the outages of the plan, recorded in the data file.
+    #[allow(dead_code)]
+    outage: usize,
+    recovery: Duration,
+}
+
+impl RecoveryTime {
+    /// `delivered` holds the bytes sent towards the client since the start of
+    /// the run, sampled during the run. The goodput before an outage falls
+    /// back to `link_bps` when nothing was sent, e.g. for an outage at the
+    /// very start.
+    ///
+    /// None when the goodput did not recover before the run ended.
+    pub fn new_from_delivered(
+        delivered: &[(Duration, u64)],
+        outage: usize,
+        window: Range<Duration>,
+        link_bps: f64,
+    ) -> Option<Self> {
+        let rate = |from: &(Duration, u64), to: &(Duration, u64)| {
+            let secs = (to.0 - from.0).as_secs_f64();
+            if secs == 0.0 {
+                return 0.0;
+            }
+            to.1.saturating_sub(from.1) as f64 * 8.0 / secs
+        };
+
+        let before: Vec<_> = delivered
+            .iter()
+            .filter(|(at, _)| *at + PRE_OUTAGE_WINDOW >= window.start && *at <= window.start)
+            .collect();
+        let before_bps = match before.as_slice() {
+            [first, .., last] => rate(first, last),
+            _ => 0.0,
+        };
+        let before_bps = if before_bps > 0.0 {
+            before_bps
+        } else {
+            link_bps
+        };
+
+        delivered
+            .windows(2)
+            .filter(|samples| samples[0].0 >= window.end)
+            .find(|samples| rate(&samples[0], &samples[1]) >= before_bps * RECOVERED_SHARE)
+            .map(|samples| RecoveryTime {
+                outage,
+                recovery: samples[1].0 - window.end,
+            })
+    }
+}
+
+impl ToStatMetric for RecoveryTime {
+    fn as_f64(&self) -> f64 {
+        self.recovery.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn recovery_time() {
+        // 1000 bytes per 100ms, nothing during the outage from 1000 to
+        // 1500ms, then a slow sample before full rate again
+        let mut delivered: Vec<_> = (0..=10).map(|i| (ms(i * 100), i * 1000)).collect();
+        delivered.extend([
+            (ms(1200), 10_000),
+            (ms(1500), 10_000),
+            (ms(1600), 10_100),
+            (ms(1700), 10_500),
+            (ms(1800), 11_500),
+        ]);
+
+        let metric =
+            RecoveryTime::new_from_delivered(&delivered, 0, ms(1000)..ms(1500), 1.0).unwrap();
+        assert_eq!(metric.recovery, ms(300));
+        assert_eq!(metric.as_f64(), 0.3);
+
+        // never back to the rate before
+        assert!(
+            RecoveryTime::new_from_delivered(&delivered[..14], 0, ms(1000)..ms(1500), 1.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn recovery_time_outage_at_start() {
+        // compared to the link rate of 80 kbit/s, 1000 bytes per 100ms
+        let delivered = [(ms(0), 0), (ms(500), 0), (ms(600), 500), (ms(700), 1500)];
+
+        let metric =
+            RecoveryTime::new_from_delivered(&delivered, 1, ms(0)..ms(500), 80_000.0).unwrap();
+        assert_eq!((metric.outage, metric.recovery), (1, ms(200)));
+    }
+}
@@ -2,7 +2,7 @@ use crate::stats::ToStatMetric;
 use regex::Regex;
 use std::fmt::Debug;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct StartupExit {
     // cwnd: usize,
     bandwidth: usize,
@@ -14,11 +14,11 @@ impl StartupExit {
     pub fn new_from_logs(logs: &[String]) -> Vec<Self> {
         let mut metrics = Vec::new();
 
+        // Regex to get "bandwidth: Some(74244584)"
+        let re = Regex::new(r"bandwidth: Some\([0-9]*\)").unwrap();
+
         for log in logs {
             // println!("{}", log);
-            // Regex to get "bandwidth: Some(74244584)"
-            let re = Regex::new(r"bandwidth: Some\([0-9]*\)").unwrap();
-
             let Some(log) = re.captures(log).map(|log| log.get(0).unwrap().as_str()) else {
                 // line did not contain path stats
                 continue;
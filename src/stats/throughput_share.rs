@@ -0,0 +1,45 @@
+use crate::stats::ToStatMetric;
+use std::fmt::Debug;
+
+/// Share of the total goodput of a run taken by one flow.
+#[derive(Default, Debug, Clone)]
+pub struct ThroughputShare {
+    /// Index into `EndpointSetup::flows`, recorded in the data file.
+    #[allow(dead_code)]
+    flow: usize,
+    share: f64,
+}
+
+impl ThroughputShare {
+    pub fn new_from_goodputs(goodputs: &[f64]) -> Vec<Self> {
+        let sum: f64 = goodputs.iter().sum();
+
+        goodputs
+            .iter()
+            .enumerate()
+            .map(|(flow, goodput)| ThroughputShare {
+                flow,
+                share: if sum == 0.0 { 0.0 } else { goodput / sum },
+            })
+            .collect()
+    }
+}
+
+impl ToStatMetric for ThroughputShare {
+    fn as_f64(&self) -> f64 {
+        self.share
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throughput_share() {
+        let metric = ThroughputShare::new_from_goodputs(&[15.0, 5.0]);
+        assert_eq!(metric.len(), 2);
+        assert_eq!((metric[0].flow, metric[0].share), (0, 0.75));
+        assert_eq!((metric[1].flow, metric[1].share), (1, 0.25));
+    }
+}